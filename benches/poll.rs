@@ -0,0 +1,105 @@
+//! Event throughput of `ShardedReactor` over local loopback, which polls
+//! each shard from its own thread.
+//!
+//! Run with `cargo bench --bench poll`.
+
+use std::{
+    io::{self, Read, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use libsrt_rs::net::{
+    Bind,
+    Builder,
+    EventKind,
+    ShardedReactor,
+    Stream,
+    Token,
+    TRANSTYPE,
+};
+
+const CONNECTIONS: usize = 256;
+const MESSAGES: usize = 200;
+const MESSAGE_SIZE: usize = 1316;
+
+fn builder() -> Builder {
+    Builder::new()
+        .trans_type(TRANSTYPE::SRTT_FILE)
+        .message_api(true)
+}
+
+fn connect_all() -> (Vec<Stream>, Vec<Stream>) {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+    let server = builder().backlog(CONNECTIONS).bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let client_thread = thread::spawn(move || {
+        (0..CONNECTIONS)
+            .map(|_| builder().connect(&addr).unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    let peers = (0..CONNECTIONS)
+        .map(|_| {
+            let (peer, _peer_addr) = server.accept().unwrap();
+            builder().nonblocking(true).accept(peer).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    (client_thread.join().unwrap(), peers)
+}
+
+fn bench(shards: usize) -> f64 {
+    let (clients, peers) = connect_all();
+
+    let peers = Arc::new(peers);
+    let received = Arc::new(AtomicUsize::new(0));
+    let expected = CONNECTIONS * MESSAGES;
+
+    let reactor = {
+        let (peers, received) = (peers.clone(), received.clone());
+        ShardedReactor::new(shards, move |_shard, event| {
+            let Token(i) = event.token();
+            let mut buf = [0; MESSAGE_SIZE];
+            loop {
+                match (&peers[i]).read(&mut buf) {
+                    Ok(_) => {
+                        received.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) => panic!("read error: {}", e),
+                }
+            }
+        }).unwrap()
+    };
+    for (i, peer) in peers.iter().enumerate() {
+        reactor.poll().register(peer, Token(i), EventKind::readable()).unwrap();
+    }
+
+    let start = Instant::now();
+    let msg = [0; MESSAGE_SIZE];
+    for _ in 0..MESSAGES {
+        for mut client in &clients {
+            client.write_all(&msg).unwrap();
+        }
+    }
+    while received.load(Ordering::SeqCst) < expected {
+        thread::sleep(Duration::from_millis(1));
+    }
+    let elapsed = start.elapsed();
+    reactor.shutdown().unwrap().unwrap();
+
+    expected as f64 / elapsed.as_secs_f64()
+}
+
+fn main() {
+    for &shards in &[1, 2, 4, 8] {
+        let rate = bench(shards);
+        println!("{} shard(s), {} connections: {:.0} messages/s",
+                 shards, CONNECTIONS, rate);
+    }
+}
@@ -1,17 +1,30 @@
 use std::env;
+use std::fs;
 use std::path::Path;
 use std::process::Command;
 
+// Oldest libsrt whose API (option numbering, struct layouts) matches ffi.rs
+const MIN_VERSION: &str = "1.5.0";
+
 fn main() {
     let target = env::var("TARGET").unwrap();
 
+    let bonding = env::var("CARGO_FEATURE_BONDING").is_ok();
+
     if env::var("LIBSRT_SYS_USE_PKG_CONFIG").is_ok() {
         let mut cfg = pkg_config::Config::new();
-        if let Ok(lib) = cfg.atleast_version("1.3.0").probe("srt") {
-            for include in &lib.include_paths {
-                println!("cargo:root={}", include.display());
+        match cfg.atleast_version(MIN_VERSION).probe("srt") {
+            Ok(lib) => {
+                for include in &lib.include_paths {
+                    println!("cargo:root={}", include.display());
+                }
+                set_version(&lib.version);
+                return;
+            }
+            Err(e) => {
+                println!("cargo:warning=system libsrt not usable ({}), \
+                          building the bundled one", e);
             }
-            return;
         }
     }
 
@@ -38,11 +51,24 @@ fn main() {
         println!("cargo:rustc-link-search=native={}/lib", trimmed_str);
     }
 
+    if bonding {
+        cmk.define("ENABLE_BONDING", "ON");
+    }
+
     let dst = cmk
         .define("ENABLE_SHARED", "OFF")
         .define("ENABLE_APPS", "OFF")
         .build();
 
+    let version_h = dst.join("include/srt/version.h");
+    let version = fs::read_to_string(&version_h)
+        .ok()
+        .and_then(|header| parse_version_h(&header))
+        .unwrap_or_else(|| {
+            panic!("cannot read SRT_VERSION_STRING from {}", version_h.display())
+        });
+    set_version(&version);
+
     println!("cargo:rustc-link-search=native={}/lib", dst.display());
     println!("cargo:rustc-link-search=native={}/lib64", dst.display());
     println!("cargo:rustc-link-lib=static=srt");
@@ -54,3 +80,39 @@ fn main() {
     }
     println!("cargo:rustc-link-lib=crypto");
 }
+
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut nums = version
+        .trim()
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|n| n.parse::<u32>());
+    match (nums.next(), nums.next(), nums.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), patch) => {
+            Some((major, minor, patch.and_then(|p| p.ok()).unwrap_or(0)))
+        }
+        _ => None,
+    }
+}
+
+fn parse_version_h(header: &str) -> Option<String> {
+    header
+        .lines()
+        .find(|line| line.starts_with("#define SRT_VERSION_STRING"))
+        .and_then(|line| line.split('"').nth(1))
+        .map(|version| version.to_string())
+}
+
+// Fails the build if the library predates the bindings and exports the
+// version the bindings were checked against to the crate.
+fn set_version(version: &str) {
+    let found = parse_version(version)
+        .unwrap_or_else(|| panic!("invalid libsrt version {:?}", version));
+    let min = parse_version(MIN_VERSION).unwrap();
+    if found < min {
+        panic!("libsrt {} is too old, the bindings require >= {}",
+               version, MIN_VERSION);
+    }
+
+    println!("cargo:rustc-env=LIBSRT_SYS_SRT_VERSION={}.{}.{}",
+             found.0, found.1, found.2);
+}
@@ -1,5 +1,5 @@
 #![allow(non_camel_case_types, unused_extern_crates, dead_code)] // XXX dead_code
-use libc::{c_char, c_void, sockaddr};
+use libc::{c_char, c_void, sockaddr, sockaddr_storage};
 
 pub use libc::c_int as int;
 pub type SRTSOCKET = int;
@@ -26,8 +26,7 @@ pub enum SRT_SOCKOPT {
     SRTO_LINGER = 7, // waiting for unsent data when closing
     SRTO_UDP_SNDBUF = 8, // UDP sending buffer size
     SRTO_UDP_RCVBUF = 9, // UDP receiving buffer size
-    // XXX Here space free for 2 options
-    // after deprecated ones are removed
+    // (some space left)
     SRTO_RENDEZVOUS = 12, // rendezvous connection mode
     SRTO_SNDTIMEO = 13,   // send() timeout
     SRTO_RCVTIMEO = 14,   // recv() timeout
@@ -39,12 +38,11 @@ pub enum SRT_SOCKOPT {
     SRTO_RCVDATA = 20,    // size of data available for recv
     SRTO_SENDER = 21, // Sender mode (independent of conn mode), for encryption, tsbpd handshake.
     SRTO_TSBPDMODE = 22, // Enable/Disable TsbPd. Enable -> Tx set origin timestamp, Rx deliver packet at origin time + delay
-    SRTO_LATENCY = 23, // DEPRECATED. SET: to both SRTO_RCVLATENCY and SRTO_PEERLATENCY. GET: same as SRTO_RCVLATENCY.
-    // SRTO_TSBPDDELAY = 23, // ALIAS: SRTO_LATENCY
+    SRTO_LATENCY = 23, // NOT RECOMMENDED. SET: to both SRTO_RCVLATENCY and SRTO_PEERLATENCY. GET: same as SRTO_RCVLATENCY.
     SRTO_INPUTBW = 24,      // Estimated input stream rate.
     SRTO_OHEADBW, // MaxBW ceiling based on % over input stream rate. Applies when UDT_MAXBW=0 (auto).
-    SRTO_PASSPHRASE = 26, // Crypto PBKDF2 Passphrase size[0,10..64] 0:disable crypto
-    SRTO_PBKEYLEN, // Crypto key len in bytes {16,24,32} Default: 16 (128-bit)
+    SRTO_PASSPHRASE = 26, // Crypto PBKDF2 Passphrase (must be 10..79 characters, or empty to disable encryption)
+    SRTO_PBKEYLEN, // Crypto key len in bytes {16,24,32} Default: 16 (AES-128)
     SRTO_KMSTATE, // Key Material exchange status (UDT_SRTKmState)
     SRTO_IPTTL = 29, // IP Time To Live (passthru for system sockopt IPPROTO_IP/IP_TTL)
     SRTO_IPTOS,   // IP Type of Service (passthru for system sockopt IPPROTO_IP/IP_TOS)
@@ -53,10 +51,10 @@ pub enum SRT_SOCKOPT {
     SRTO_NAKREPORT = 33, // Enable receiver to send periodic NAK reports
     SRTO_VERSION = 34, // Local SRT Version
     SRTO_PEERVERSION, // Peer SRT Version (from SRT Handshake)
-    SRTO_CONNTIMEO = 36, // Connect timeout in msec. Ccaller default: 3000, rendezvous (x 10)
-    // deprecated: SRTO_TWOWAYDATA, SRTO_SNDPBKEYLEN, SRTO_RCVPBKEYLEN (@c below)
-    _DEPRECATED_SRTO_SNDPBKEYLEN = 38, // (needed to use inside the code without generating -Wswitch)
-    //
+    SRTO_CONNTIMEO = 36, // Connect timeout in msec. Caller default: 3000, rendezvous (x 10)
+    SRTO_DRIFTTRACER = 37, // Enable or disable drift tracer
+    SRTO_MININPUTBW = 38, // Minimum estimate of input stream rate.
+    // (some space left)
     SRTO_SNDKMSTATE = 40, // (GET) the current state of the encryption at the peer side
     SRTO_RCVKMSTATE,      // (GET) the current state of the encryption at the agent side
     SRTO_LOSSMAXTTL, // Maximum possible packet reorder tolerance (number of packets to receive after loss to send lossreport)
@@ -64,13 +62,22 @@ pub enum SRT_SOCKOPT {
     SRTO_PEERLATENCY, // Minimum value of the TsbPd receiver delay (mSec) for the opposite side (peer)
     SRTO_MINVERSION, // Minimum SRT version needed for the peer (peers with less version will get connection reject)
     SRTO_STREAMID,   // A string set to a socket and passed to the listener's accepted socket
-    SRTO_SMOOTHER,   // Smoother selection (congestion control algorithm)
+    SRTO_CONGESTION, // Congestion controller type selection
     SRTO_MESSAGEAPI, // In File mode, use message API (portions of data with boundaries)
     SRTO_PAYLOADSIZE, // Maximum payload size sent in one UDP packet (0 if unlimited)
-    SRTO_TRANSTYPE,  // Transmission type (set of options required for given transmission type)
+    SRTO_TRANSTYPE = 50, // Transmission type (set of options required for given transmission type)
     SRTO_KMREFRESHRATE, // After sending how many packets the encryption key should be flipped to the new key
     SRTO_KMPREANNOUNCE, // How many packets before key flip the new key is annnounced and after key flip the old one decommissioned
-    SRTO_STRICTENC, // Connection to be rejected or quickly broken when one side encryption set or bad password
+    SRTO_ENFORCEDENCRYPTION, // Connection to be rejected or quickly broken when one side encryption set or bad password
+    SRTO_IPV6ONLY,   // IPV6_V6ONLY mode
+    SRTO_PEERIDLETIMEO, // Peer-idle timeout (max time of silence heard from peer) in [ms]
+    SRTO_BINDTODEVICE, // Forward the SOL_SOCKET/SO_BINDTODEVICE option on socket (pass packets only from that device)
+    SRTO_GROUPCONNECT, // Set on a listener to allow group connection
+    SRTO_GROUPMINSTABLETIMEO, // Minimum Link Stability timeout (backup mode) in milliseconds
+    SRTO_GROUPTYPE,  // Group type to which an accepted socket is about to be added, available in the handshake
+    SRTO_PACKETFILTER = 60, // Add and configure a packet filter
+    SRTO_RETRANSMITALGO = 61, // An option to select packet retransmission algorithm
+    SRTO_CRYPTOMODE = 62, // Encryption cipher mode (AES-CTR, AES-GCM, ...), since 1.5.2
 }
 
 #[repr(C)]
@@ -81,7 +88,31 @@ pub enum SRT_TRANSTYPE {
     SRTT_INVALID,
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SRT_KM_STATE {
+    SRT_KM_S_UNSECURED = 0, // No encryption
+    SRT_KM_S_SECURING = 1,  // Stream encrypted, exchanging Keying Material
+    SRT_KM_S_SECURED = 2,   // Stream encrypted, keying Material exchanged, decrypting ok.
+    SRT_KM_S_NOSECRET = 3,  // Stream encrypted and no secret to decrypt Keying Material
+    SRT_KM_S_BADSECRET = 4, // Stream encrypted and wrong secret, cannot decrypt Keying Material
+    SRT_KM_S_BADCRYPTOMODE = 5, // Stream encrypted but wrong cryptographic mode is used (since 1.5.2)
+}
+
+// SRTO_CRYPTOMODE values
+pub const SRT_CRYPTOMODE_AUTO: int = 0;
+pub const SRT_CRYPTOMODE_AES_CTR: int = 1;
+pub const SRT_CRYPTOMODE_AES_GCM: int = 2;
+
+// AES-GCM authentication tag appended to every encrypted payload
+pub const HAICRYPT_AUTHTAG_MAX: int = 16;
+
+pub const SRT_LIVE_DEF_PLSIZE: int = 1316; // = 188*7, recommended for MPEG TS
+pub const SRT_LIVE_MAX_PLSIZE: int = 1456; // MTU(1500) - UDP.hdr(28) - SRT.hdr(16)
+pub const SRT_LIVE_DEF_LATENCY_MS: int = 120;
+
 pub const SRT_INVALID_SOCK: SRTSOCKET = -1;
+pub const SRTGROUP_MASK: SRTSOCKET = 1 << 30;
 pub const SRT_ERROR: int = -1;
 
 // library initialization
@@ -92,10 +123,9 @@ extern "C" {
 
 // socket operations
 extern "C" {
-    pub fn srt_socket(af: int, typ: int, protocol: int) -> SRTSOCKET;
     pub fn srt_create_socket() -> SRTSOCKET;
     pub fn srt_bind(u: SRTSOCKET, name: *const sockaddr, namelen: int) -> int;
-    pub fn srt_bind_peerof(u: SRTSOCKET, udpsock: UDPSOCKET) -> int;
+    pub fn srt_bind_acquire(u: SRTSOCKET, sys_udp_sock: UDPSOCKET) -> int;
     pub fn srt_listen(u: SRTSOCKET, backlog: int) -> int;
     pub fn srt_accept(
         u: SRTSOCKET,
@@ -107,6 +137,12 @@ extern "C" {
         name: *const sockaddr,
         namelen: int
     ) -> int;
+    pub fn srt_connect_bind(
+        u: SRTSOCKET,
+        source: *const sockaddr,
+        target: *const sockaddr,
+        len: int,
+    ) -> int;
     pub fn srt_connect_debug(
         u: SRTSOCKET,
         name: *const sockaddr,
@@ -159,16 +195,17 @@ extern "C" {
     ) -> int;
 }
 
-// XXX Note that the srctime functionality doesn't work yet and needs fixing.
 #[repr(C)]
 pub struct SRT_MSGCTRL {
-    flags: int,    // Left for future
-    msgttl: int,   // TTL for a message, default -1 (delivered always)
-    inorder: int, // Whether a message is allowed to supersede partially lost one. Unused in stream and live mode.
-    boundary: int, //0:mid pkt, 1(01b):end of frame, 2(11b):complete frame, 3(10b): start of frame
-    srctime: u64, // source timestamp (usec), 0: use internal time
-    pktseq: i32,  // sequence number of the first packet in received message (unused for sending)
-    msgno: i32,   // message number (output value for both sending and receiving)
+    pub flags: int,    // Left for future
+    pub msgttl: int,   // TTL for a message (millisec), default -1 (no TTL limitation)
+    pub inorder: int, // Whether a message is allowed to supersede partially lost one. Unused in stream and live mode.
+    pub boundary: int, // 0:mid pkt, 1(01b):end of frame, 2(11b):complete frame, 3(10b): start of frame
+    pub srctime: i64, // source time since epoch (usec), 0: use internal time (sender)
+    pub pktseq: i32,  // sequence number of the first packet in received message (unused for sending)
+    pub msgno: i32,   // message number (output value for both sending and receiving)
+    pub grpdata: *mut SRT_SOCKGROUPDATA,
+    pub grpdata_size: usize,
 }
 
 // You are free to use either of these two methods to set SRT_MSGCTRL object
@@ -178,6 +215,74 @@ extern "C" {
     pub static srt_msgctrl_default: SRT_MSGCTRL;
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SRT_GROUP_TYPE {
+    SRT_GTYPE_UNDEFINED,
+    SRT_GTYPE_BROADCAST,
+    SRT_GTYPE_BACKUP,
+    SRT_GTYPE_E_END,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SRT_MEMBERSTATUS {
+    SRT_GST_PENDING, // The socket is created correctly, but not yet ready for getting data.
+    SRT_GST_IDLE,    // The socket is ready to be activated
+    SRT_GST_RUNNING, // The socket was already activated and is in use
+    SRT_GST_BROKEN,  // The last operation broke the socket, it should be closed.
+}
+
+#[repr(C)]
+pub struct SRT_SOCKGROUPDATA {
+    pub id: SRTSOCKET,
+    pub peeraddr: sockaddr_storage,
+    pub sockstate: SRT_SOCKSTATUS,
+    pub weight: u16,
+    pub memberstate: SRT_MEMBERSTATUS,
+    pub result: int,
+    pub token: int,
+}
+
+pub enum SRT_SOCKOPT_CONFIG {}
+
+#[repr(C)]
+pub struct SRT_SOCKGROUPCONFIG {
+    pub id: SRTSOCKET,
+    pub srcaddr: sockaddr_storage,
+    pub peeraddr: sockaddr_storage,
+    pub weight: u16,
+    pub config: *mut SRT_SOCKOPT_CONFIG,
+    pub errorcode: int,
+    pub token: int,
+}
+
+// exported by every libsrt, fails unless it is built with ENABLE_BONDING
+extern "C" {
+    pub fn srt_create_group(gt: SRT_GROUP_TYPE) -> SRTSOCKET;
+}
+
+// socket groups (libsrt built with ENABLE_BONDING)
+#[cfg(feature = "bonding")]
+extern "C" {
+    pub fn srt_groupof(socket: SRTSOCKET) -> SRTSOCKET;
+    pub fn srt_group_data(
+        socketgroup: SRTSOCKET,
+        output: *mut SRT_SOCKGROUPDATA,
+        inoutlen: *mut usize,
+    ) -> int;
+    pub fn srt_prepare_endpoint(
+        src: *const sockaddr,
+        adr: *const sockaddr,
+        namelen: int,
+    ) -> SRT_SOCKGROUPCONFIG;
+    pub fn srt_connect_group(
+        group: SRTSOCKET,
+        name: *mut SRT_SOCKGROUPCONFIG,
+        arraysize: int,
+    ) -> int;
+}
+
 // enum CodeMajor
 pub const MJ_UNKNOWN   : int = -1;
 pub const MJ_SUCCESS   : int =  0;
@@ -196,12 +301,14 @@ pub const MN_TIMEOUT        : int =  1;
 pub const MN_REJECTED       : int =  2;
 pub const MN_NORES          : int =  3;
 pub const MN_SECURITY       : int =  4;
+pub const MN_CLOSED         : int =  5;
 // MJ_CONNECTION
 pub const MN_CONNLOST       : int =  1;
 pub const MN_NOCONN         : int =  2;
 // MJ_SYSTEMRES
 pub const MN_THREAD         : int =  1;
 pub const MN_MEMORY         : int =  2;
+pub const MN_OBJECT         : int =  3;
 // MJ_FILESYSTEM
 pub const MN_SEEKGFAIL      : int =  1;
 pub const MN_READFAIL       : int =  2;
@@ -221,25 +328,102 @@ pub const MN_INVALBUFFERAPI : int = 10;
 pub const MN_BUSY           : int = 11;
 pub const MN_XSIZE          : int = 12;
 pub const MN_EIDINVAL       : int = 13;
+pub const MN_EEMPTY         : int = 14;
+pub const MN_BUSYPORT       : int = 15;
 // MJ_AGAIN
 pub const MN_WRAVAIL        : int =  1;
 pub const MN_RDAVAIL        : int =  2;
 pub const MN_XMTIMEOUT      : int =  3;
 pub const MN_CONGESTION     : int =  4;
 
-// enum SRT_ERRNO
-pub const SRT_ETIMEOUT       : int = 6003; // XXX MJ_AGAIN * 1000 + XMTIMEOUT
+// enum SRT_ERRNO (MJ_* * 1000 + MN_*)
+pub const SRT_ECONNREJ       : int = 1002;
+pub const SRT_ECONNLOST      : int = 2001;
+pub const SRT_ENOCONN        : int = 2002;
+pub const SRT_EINVPARAM      : int = 5003;
+pub const SRT_ELARGEMSG      : int = 5012;
+pub const SRT_EASYNCSND      : int = 6001;
+pub const SRT_EASYNCRCV      : int = 6002;
+pub const SRT_ETIMEOUT       : int = 6003;
+pub const SRT_ECONGEST       : int = 6004;
 
 // The send/receive functions.
 extern "C" {
-    pub fn srt_sendmsg(u: SRTSOCKET, buf: *const c_char, len: int) -> int;
+    pub fn srt_send(u: SRTSOCKET, buf: *const c_char, len: int) -> int;
+    pub fn srt_sendmsg(
+        u: SRTSOCKET,
+        buf: *const c_char,
+        len: int,
+        ttl: int, /* = -1 */
+        inorder: int, /* = false */
+    ) -> int;
+    pub fn srt_sendmsg2(
+        u: SRTSOCKET,
+        buf: *const c_char,
+        len: int,
+        mctrl: *mut SRT_MSGCTRL,
+    ) -> int;
+    pub fn srt_recv(u: SRTSOCKET, buf: *mut c_char, len: int) -> int;
     pub fn srt_recvmsg(u: SRTSOCKET, buf: *mut c_char, len: int) -> int;
+    pub fn srt_recvmsg2(
+        u: SRTSOCKET,
+        buf: *mut c_char,
+        len: int,
+        mctrl: *mut SRT_MSGCTRL,
+    ) -> int;
 }
 
 // last error detection
 extern "C" {
     pub fn srt_getlasterror(errno_loc: *mut int) -> int;
     pub fn srt_strerror(code: int, errnoval: int) -> *const c_char;
+    pub fn srt_clearlasterror();
+}
+
+// Reasons of a connection rejection, see srt_getrejectreason()
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SRT_REJECT_REASON {
+    SRT_REJ_UNKNOWN,    // initial set when in progress
+    SRT_REJ_SYSTEM,     // broken due to system function error
+    SRT_REJ_PEER,       // connection was rejected by peer
+    SRT_REJ_RESOURCE,   // internal problem with resource allocation
+    SRT_REJ_ROGUE,      // incorrect data in handshake messages
+    SRT_REJ_BACKLOG,    // listener's backlog exceeded
+    SRT_REJ_IPE,        // internal program error
+    SRT_REJ_CLOSE,      // socket is closing
+    SRT_REJ_VERSION,    // peer is older version than agent's minimum set
+    SRT_REJ_RDVCOOKIE,  // rendezvous cookie collision
+    SRT_REJ_BADSECRET,  // wrong password
+    SRT_REJ_UNSECURE,   // password required or unexpected
+    SRT_REJ_MESSAGEAPI, // streamapi/messageapi collision
+    SRT_REJ_CONGESTION, // incompatible congestion-controller type
+    SRT_REJ_FILTER,     // incompatible packet filter
+    SRT_REJ_GROUP,      // incompatible group
+    SRT_REJ_TIMEOUT,    // connection timeout
+    SRT_REJ_CRYPTO,     // conflicting cryptographic configurations (1.5.2)
+}
+
+extern "C" {
+    pub fn srt_getrejectreason(sock: SRTSOCKET) -> int;
+    pub fn srt_setrejectreason(sock: SRTSOCKET, value: int) -> int;
+    pub fn srt_rejectreason_str(id: int) -> *const c_char;
+}
+
+// library version, 0xXXYYZZ (major, minor, patch)
+extern "C" {
+    pub fn srt_getversion() -> u32;
+}
+
+// buffer and time queries
+extern "C" {
+    pub fn srt_getsndbuffer(
+        sock: SRTSOCKET,
+        blocks: *mut usize,
+        bytes: *mut usize
+    ) -> int;
+    pub fn srt_time_now() -> i64;
+    pub fn srt_connection_time(sock: SRTSOCKET) -> i64;
 }
 
 // XXX
@@ -266,6 +450,7 @@ pub fn srt_errorkind(errcode: int) -> std::io::ErrorKind {
         MJ_NOTSUP => {
             match minor {
                 MN_BUSY => std::io::ErrorKind::AlreadyExists,
+                MN_XSIZE => std::io::ErrorKind::InvalidInput,
                 _ => std::io::ErrorKind::Other,
             }
         },
@@ -308,6 +493,21 @@ pub enum SRT_EPOLL_OPT {
     SRT_EPOLL_IN = 0x1,
     SRT_EPOLL_OUT = 0x4,
     SRT_EPOLL_ERR = 0x8,
+    SRT_EPOLL_UPDATE = 0x10, // (listener) a new group member connection was accepted
+}
+
+// edge-triggered mode, out of SRT_EPOLL_OPT range for a C int enum
+pub const SRT_EPOLL_ET: int = 1 << 31;
+
+// srt_epoll_set() flags
+pub const SRT_EPOLL_ENABLE_EMPTY: i32 = 1;
+pub const SRT_EPOLL_ENABLE_OUTPUT: i32 = 2;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SRT_EPOLL_EVENT {
+    pub fd: SRTSOCKET,
+    pub events: int,
 }
 
 extern "C" {
@@ -346,5 +546,13 @@ extern "C" {
         lw_fds: *mut SYSSOCKET,
         lw_num: *mut int,
     ) -> int;
+    pub fn srt_epoll_uwait(
+        epid: int,
+        fds_set: *mut SRT_EPOLL_EVENT,
+        fds_size: int,
+        ms_timeout: i64,
+    ) -> int;
+    pub fn srt_epoll_clear_usocks(epid: int) -> int;
+    pub fn srt_epoll_set(epid: int, flags: i32) -> i32;
     pub fn srt_epoll_release(epid: int) -> int;
 }
@@ -0,0 +1,200 @@
+use std::{
+    io::{self, IoSlice, IoSliceMut},
+    mem,
+    net::SocketAddr,
+    ptr,
+};
+use libc::{c_int as int, sockaddr_storage, socklen_t};
+
+use crate::error as err;
+pub use crate::ffi::{SRT_GROUP_TYPE, SRT_MEMBERSTATUS};
+use crate::ffi::{self, SRT_SOCKSTATUS, SRTSOCKET};
+use crate::socket::{from_sockaddr, into_sockaddr, Socket};
+
+/// A group of SRT sockets (connection bonding) sending and receiving as
+/// a single socket.
+#[derive(Debug)]
+pub struct Group(Socket);
+
+impl Group {
+    pub fn new(group_type: SRT_GROUP_TYPE) -> io::Result<Group> {
+        let grp = unsafe { err::cvt(ffi::srt_create_group(group_type))? };
+        Ok(Group(Socket::from_raw(grp)))
+    }
+
+    /// Wraps a socket that refers to a group, e.g. one accepted by a
+    /// listener with group connections enabled.
+    pub fn from_socket(sock: Socket) -> Result<Group, Socket> {
+        if is_group(sock.as_raw()) {
+            Ok(Group(sock))
+        } else {
+            Err(sock)
+        }
+    }
+
+    pub fn as_socket(&self) -> &Socket {
+        &self.0
+    }
+
+    pub fn as_raw(&self) -> SRTSOCKET {
+        self.0.as_raw()
+    }
+
+    /// Connects the group to all endpoints and returns the first member
+    /// that got connected (or is connecting in nonblocking mode).
+    pub fn connect(&self, endpoints: &[Endpoint]) -> io::Result<SRTSOCKET> {
+        let mut configs = endpoints
+            .iter()
+            .map(|endpoint| {
+                let (peerp, len) = into_sockaddr(&endpoint.peer);
+                let srcp = match endpoint.source {
+                    Some(ref source) => into_sockaddr(source).0,
+                    None => ptr::null(),
+                };
+                let mut config = unsafe {
+                    ffi::srt_prepare_endpoint(srcp, peerp, len as int)
+                };
+                config.weight = endpoint.weight;
+                config
+            })
+            .collect::<Vec<_>>();
+        let member = unsafe {
+            err::cvt(ffi::srt_connect_group(
+                self.as_raw(),
+                configs.as_mut_ptr(),
+                configs.len() as int,
+            ))?
+        };
+        Ok(member)
+    }
+
+    /// Returns the current status of all group members.
+    pub fn members(&self) -> io::Result<Vec<Member>> {
+        let mut len: usize = 4;
+        loop {
+            let mut data: Vec<ffi::SRT_SOCKGROUPDATA> = Vec::with_capacity(len);
+            let mut inoutlen = len;
+            let ret = unsafe {
+                ffi::srt_group_data(self.as_raw(), data.as_mut_ptr(), &mut inoutlen)
+            };
+            if ret == ffi::SRT_ERROR {
+                if inoutlen > len {
+                    len = inoutlen;
+                    continue;
+                }
+                let err = err::Error::last_error();
+                return Err(io::Error::new(err.kind(), err));
+            }
+            unsafe { data.set_len(inoutlen) };
+            return Ok(data.iter().map(Member::from_data).collect());
+        }
+    }
+
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.recv(buf)
+    }
+
+    pub fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.0.recv_vectored(bufs)
+    }
+
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.0.send(buf)
+    }
+
+    pub fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.0.send_vectored(bufs)
+    }
+}
+
+/// Returns true if the socket id refers to a group.
+pub fn is_group(sock: SRTSOCKET) -> bool {
+    sock != ffi::SRT_INVALID_SOCK && (sock & ffi::SRTGROUP_MASK) != 0
+}
+
+/// A remote endpoint a group member connects to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    source: Option<SocketAddr>,
+    peer: SocketAddr,
+    weight: u16,
+}
+
+impl Endpoint {
+    pub fn new(peer: SocketAddr) -> Endpoint {
+        Endpoint {
+            source: None,
+            peer: peer,
+            weight: 0,
+        }
+    }
+
+    /// Local address the member connection is bound to
+    pub fn source(mut self, source: SocketAddr) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Member weight (priority in backup groups, higher is preferred)
+    pub fn weight(mut self, weight: u16) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+}
+
+/// Status of a single group member.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Member {
+    id: SRTSOCKET,
+    peer_addr: Option<SocketAddr>,
+    state: SRT_SOCKSTATUS,
+    status: SRT_MEMBERSTATUS,
+    weight: u16,
+    result: int,
+}
+
+impl Member {
+    fn from_data(data: &ffi::SRT_SOCKGROUPDATA) -> Member {
+        let storage: &sockaddr_storage = &data.peeraddr;
+        Member {
+            id: data.id,
+            peer_addr: from_sockaddr(storage, mem::size_of_val(storage) as socklen_t).ok(),
+            state: data.sockstate,
+            status: data.memberstate,
+            weight: data.weight,
+            result: data.result,
+        }
+    }
+
+    pub fn id(&self) -> SRTSOCKET {
+        self.id
+    }
+
+    /// Address of the peer, unknown while the member is still pending
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Socket state of the member connection
+    pub fn state(&self) -> SRT_SOCKSTATUS {
+        self.state
+    }
+
+    /// Member status within the group
+    pub fn status(&self) -> SRT_MEMBERSTATUS {
+        self.status
+    }
+
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    /// Result of the last operation on the member (-1 if it failed)
+    pub fn result(&self) -> int {
+        self.result
+    }
+}
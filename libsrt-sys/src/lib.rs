@@ -1,7 +1,12 @@
 mod error;
 mod ffi;
+#[cfg(feature = "bonding")]
+mod group;
 mod poll;
 mod socket;
+mod version;
+
+use std::io;
 
 extern "C" fn cleanup() {
     unsafe {
@@ -9,22 +14,81 @@ extern "C" fn cleanup() {
     }
 }
 
+/// Starts up libsrt once per process.
+///
+/// An incompatible libsrt is not started, see `try_init` to find out.
 pub fn init() {
-    use std::sync::Once;
+    drop(try_init());
+}
+
+/// Starts up libsrt once per process, failing without starting it if the
+/// linked libsrt is incompatible.
+///
+/// The version is checked by the first call, later ones return the same
+/// result.
+pub fn try_init() -> io::Result<()> {
+    use std::sync::OnceLock;
+
+    static INIT: OnceLock<Result<(), String>> = OnceLock::new();
+
+    let res = INIT.get_or_init(|| {
+        check_version().map_err(|e| e.to_string())?;
+        unsafe {
+            crate::ffi::srt_startup();
+            libc::atexit(cleanup);
+        }
+        Ok(())
+    });
+    res.clone().map_err(io::Error::other)
+}
+
+/// Version of libsrt the bindings were built against (`major.minor.patch`).
+pub const BUILD_VERSION: &str = env!("LIBSRT_SYS_SRT_VERSION");
 
-    static INIT: Once = Once::new();
+// The option and struct layouts differ between minor releases, so a
+// dynamically linked libsrt must match the headers we were built with.
+fn check_version() -> io::Result<()> {
+    let linked = version();
+    let built: Version = BUILD_VERSION.parse().unwrap();
 
-    INIT.call_once(|| unsafe {
-        crate::ffi::srt_startup();
-        libc::atexit(cleanup);
-    })
+    if (linked.major, linked.minor) != (built.major, built.minor) {
+        return Err(io::Error::other(
+            format!("libsrt {} is linked, but libsrt-sys was built against {}",
+                    linked, built),
+        ));
+    }
+    Ok(())
 }
 
 pub use libc::c_int as int;
+pub use ffi::{
+    SRTSOCKET,
+    SRT_CRYPTOMODE_AES_CTR as CRYPTOMODE_AES_CTR,
+    SRT_CRYPTOMODE_AES_GCM as CRYPTOMODE_AES_GCM,
+    SRT_CRYPTOMODE_AUTO as CRYPTOMODE_AUTO,
+    HAICRYPT_AUTHTAG_MAX as AUTHTAG_MAX,
+};
+#[cfg(feature = "bonding")]
+pub use group::{
+    is_group,
+    Endpoint,
+    Group,
+    Member,
+    SRT_GROUP_TYPE as GROUPTYPE,
+    SRT_MEMBERSTATUS as MEMBERSTATUS,
+};
 pub use poll::{Event, EventKind, Events, Poll, Token};
+pub use version::{capabilities, version, Capabilities, Version};
 pub use socket::{
+    rejectreason_str,
+    MsgInfo,
+    Observer,
+    sndbuffer,
+    sockstate,
     Socket,
+    SRT_KM_STATE as KM_STATE,
     SRT_SOCKSTATUS as SOCKSTATUS,
     SRT_TRANSTYPE as TRANSTYPE,
     SRT_LIVE_DEF_PLSIZE as LIVE_DEF_PLSIZE,
+    SRT_LIVE_MAX_PLSIZE as LIVE_MAX_PLSIZE,
 };
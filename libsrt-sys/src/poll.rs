@@ -7,16 +7,25 @@ use std::{
     iter::FromIterator,
     ops,
     ptr,
-    sync::RwLock,
+    sync::{atomic::AtomicBool, Arc, RwLock, Weak},
     time::Duration,
 };
 use crate::error as err;
 use crate::ffi::{self, int, SRT_ETIMEOUT, SRTSOCKET};
-use crate::socket::Socket;
+use crate::socket::{report_broken, Observer, Socket};
 
 pub struct Poll {
     epid: int,
     socks: RwLock<HashMap<SRTSOCKET, Token>>, // XXX or RefCell
+    watchers: RwLock<HashMap<SRTSOCKET, Watcher>>,
+}
+
+/// Observer of a registered connection, told when an error event finds it
+/// broken.
+struct Watcher {
+    observer: Arc<dyn Observer>,
+    /// Dead once the socket is dropped
+    broken: Weak<AtomicBool>,
 }
 
 /// Polls for readiness events on all registered sockets.
@@ -27,6 +36,7 @@ impl Poll {
         Ok(Poll {
             epid: epid,
             socks: RwLock::new(HashMap::new()),
+            watchers: RwLock::new(HashMap::new()),
         })
     }
 
@@ -36,12 +46,25 @@ impl Poll {
         sock: &Socket,
         token: Token,
         event: EventKind
+    ) -> io::Result<()> {
+        self.register_raw(sock.as_raw(), token, event)?;
+        self.watch(sock);
+        Ok(())
+    }
+
+    /// Register a socket by its id, when the `Socket` itself is not at
+    /// hand.
+    pub fn register_raw(
+        &self,
+        sock: SRTSOCKET,
+        token: Token,
+        event: EventKind
     ) -> io::Result<()> {
         let e = event.0;
         err::cvt(unsafe {
-            ffi::srt_epoll_add_usock(self.epid, sock.as_raw(), &e)
+            ffi::srt_epoll_add_usock(self.epid, sock, &e)
         })?;
-        self.socks.write().unwrap().insert(sock.as_raw(), token);
+        self.socks.write().unwrap().insert(sock, token);
         Ok(())
     }
 
@@ -57,14 +80,38 @@ impl Poll {
             ffi::srt_epoll_update_usock(self.epid, sock.as_raw(), &e)
         })?;
         self.socks.write().unwrap().insert(sock.as_raw(), token);
+        self.watch(sock);
+        Ok(())
+    }
+
+    /// Re-register a socket by its id, when the `Socket` itself is not at
+    /// hand.
+    pub fn reregister_raw(
+        &self,
+        sock: SRTSOCKET,
+        token: Token,
+        event: EventKind
+    ) -> io::Result<()> {
+        let e = event.0;
+        err::cvt(unsafe {
+            ffi::srt_epoll_update_usock(self.epid, sock, &e)
+        })?;
+        self.socks.write().unwrap().insert(sock, token);
         Ok(())
     }
 
     /// Deregister the socket from the `Poll` instance.
     pub fn deregister(&self, sock: &Socket) -> io::Result<()> {
-        self.socks.write().unwrap().remove(&(sock.as_raw()));
+        self.deregister_raw(sock.as_raw())
+    }
+
+    /// Deregister a socket by its id, when the `Socket` itself is not at
+    /// hand.
+    pub fn deregister_raw(&self, sock: SRTSOCKET) -> io::Result<()> {
+        self.socks.write().unwrap().remove(&sock);
+        self.watchers.write().unwrap().remove(&sock);
         err::cvt(unsafe {
-            ffi::srt_epoll_remove_usock(self.epid, sock.as_raw())
+            ffi::srt_epoll_remove_usock(self.epid, sock)
         })?;
         Ok(())
     }
@@ -149,31 +196,13 @@ impl Poll {
             if sock == ffi::SRT_INVALID_SOCK {
                 continue;
             }
-            if wr_socks_set.contains(&sock) {
-                wr_socks_set.remove(&sock);
-                if srt_is_closed(sock) {
-                    new_evts.push(Event::new(
-                        *(self.socks.read().unwrap().get(&sock).unwrap()),
-                        EventKind::error(),
-                    ));
-                } else {
-                    new_evts.push(Event::new(
-                        *(self.socks.read().unwrap().get(&sock).unwrap()),
-                        EventKind::readable() | EventKind::writable(),
-                    ));
-                }
+            let kind = if wr_socks_set.remove(&sock) {
+                EventKind::readable() | EventKind::writable()
             } else {
-                if srt_is_closed(sock) {
-                    new_evts.push(Event::new(
-                        *(self.socks.read().unwrap().get(&sock).unwrap()),
-                        EventKind::error(),
-                    ));
-                } else {
-                    new_evts.push(Event::new(
-                        *(self.socks.read().unwrap().get(&sock).unwrap()),
-                        EventKind::readable(),
-                    ));
-                }
+                EventKind::readable()
+            };
+            if let Some(event) = self.event(sock, kind) {
+                new_evts.push(event);
             }
         }
 
@@ -182,16 +211,8 @@ impl Poll {
                 continue;
             }
             if wr_socks_set.contains(&sock) {
-                if srt_is_closed(sock) {
-                    new_evts.push(Event::new(
-                        *(self.socks.read().unwrap().get(&sock).unwrap()),
-                        EventKind::error(),
-                    ));
-                } else {
-                    new_evts.push(Event::new(
-                        *(self.socks.read().unwrap().get(&sock).unwrap()),
-                        EventKind::writable(),
-                    ));
+                if let Some(event) = self.event(sock, EventKind::writable()) {
+                    new_evts.push(event);
                 }
             }
         }
@@ -202,6 +223,42 @@ impl Poll {
 
         Ok(evts_len)
     }
+
+    /// Keeps the observer of a connected socket, so it hears about the
+    /// connection breaking; failed connects are reported by whoever
+    /// completes them.
+    ///
+    /// Raw registrations keep the watcher of an earlier one.
+    fn watch(&self, sock: &Socket) {
+        let (observer, broken) = match sock.watcher() {
+            Some(watcher) if sock.state() == ffi::SRT_SOCKSTATUS::SRTS_CONNECTED => watcher,
+            _ => return,
+        };
+        let mut watchers = self.watchers.write().unwrap();
+        // forget sockets dropped without being deregistered, whenever the
+        // map doubled in size
+        if watchers.len().is_power_of_two() {
+            watchers.retain(|_, watcher| watcher.broken.strong_count() > 0);
+        }
+        watchers.insert(sock.as_raw(), Watcher { observer: observer, broken: broken });
+    }
+
+    /// Event of a polled socket, `None` if it was deregistered meanwhile
+    /// (e.g. migrated by another thread). A socket found closed gets an
+    /// error event instead, telling its observer once.
+    fn event(&self, sock: SRTSOCKET, kind: EventKind) -> Option<Event> {
+        let token = *self.socks.read().unwrap().get(&sock)?;
+        if !srt_is_closed(sock) {
+            return Some(Event::new(token, kind));
+        }
+        let watcher = self.watchers.write().unwrap().remove(&sock);
+        if let Some(watcher) = watcher {
+            if let Some(broken) = watcher.broken.upgrade() {
+                report_broken(&*watcher.observer, &broken, sock);
+            }
+        }
+        Some(Event::new(token, EventKind::error()))
+    }
 }
 
 impl Drop for Poll {
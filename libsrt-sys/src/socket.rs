@@ -1,8 +1,16 @@
 use std::{
+    cmp,
     ffi::CStr,
+    fmt,
     io::{self, IoSlice, IoSliceMut},
     mem,
-    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    ptr,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+    time::Duration,
 };
 use libc::{
     self as c, c_char, c_int as int, sockaddr, sockaddr_in, sockaddr_in6, sockaddr_storage,
@@ -10,51 +18,137 @@ use libc::{
 };
 
 use crate::error as err;
-pub use crate::ffi::{SRT_SOCKSTATUS, SRT_TRANSTYPE};
+pub use crate::ffi::{SRT_KM_STATE, SRT_SOCKSTATUS, SRT_TRANSTYPE};
 use crate::ffi::{self, SRTSOCKET};
+use crate::version::Version;
+
+pub const SRT_LIVE_DEF_PLSIZE: usize = ffi::SRT_LIVE_DEF_PLSIZE as usize; // = 188*7, recommended for MPEG TS
+pub const SRT_LIVE_MAX_PLSIZE: usize = ffi::SRT_LIVE_MAX_PLSIZE as usize;
+
+/// Hooks notified about the lifecycle and traffic of a socket, e.g. to
+/// collect metrics or write an audit log (see `Socket::set_observer`).
+///
+/// The hooks run on the thread making the call that triggers them, so they
+/// should return quickly and must not call back into the socket.
+pub trait Observer: Send + Sync {
+    /// A connection to `peer` is about to be initiated.
+    fn connecting(&self, _sock: SRTSOCKET, _peer: &SocketAddr) {}
+
+    /// The handshake with `peer` completed.
+    ///
+    /// A nonblocking connect only starts the handshake, whoever completes
+    /// it is responsible for reporting the outcome (see
+    /// `Socket::connect_complete`).
+    fn connected(&self, _sock: SRTSOCKET, _peer: &SocketAddr) {}
+
+    /// Connecting to `peer` failed.
+    fn connect_failed(&self, _sock: SRTSOCKET, _peer: &SocketAddr, _err: &io::Error) {}
+
+    /// `listener` accepted a connection from `peer`, which inherits the
+    /// listener's observer.
+    fn accepted(&self, _listener: SRTSOCKET, _sock: SRTSOCKET, _peer: &SocketAddr) {}
+
+    /// The connection was found broken, by a `Poll` error event, a failing
+    /// send or receive or at the latest when the socket is closed;
+    /// reported once.
+    fn broken(&self, _sock: SRTSOCKET) {}
+
+    /// The socket was closed, after which its id may be reused.
+    fn closed(&self, _sock: SRTSOCKET) {}
+
+    /// Payload bytes were handed over to SRT for sending.
+    fn sent(&self, _sock: SRTSOCKET, _bytes: usize) {}
+
+    /// Payload bytes were received.
+    fn received(&self, _sock: SRTSOCKET, _bytes: usize) {}
+}
 
-pub const SRT_LIVE_DEF_PLSIZE: usize = 1316; // = 188*7, recommended for MPEG TS
-
-#[derive(Debug)]
-pub struct Socket(SRTSOCKET);
+pub struct Socket {
+    id: SRTSOCKET,
+    observer: Option<Arc<dyn Observer>>,
+    /// Whether the observer was told about the broken connection, shared
+    /// with the polls the socket is registered on
+    broken: Arc<AtomicBool>,
+}
 
 impl Socket {
-    pub fn new(addr: &SocketAddr) -> io::Result<Socket> {
-        let fam = match *addr {
-            SocketAddr::V4(..) => c::AF_INET,
-            SocketAddr::V6(..) => c::AF_INET6,
-        };
-        Socket::new_raw(fam)
+    /// Creates a new socket. The address family is decided by the address
+    /// the socket is bound or connected to.
+    pub fn new() -> io::Result<Socket> {
+        let sock = unsafe { err::cvt(ffi::srt_create_socket())? };
+        Ok(Socket::from_raw(sock))
     }
 
-    fn new_raw(af: int) -> io::Result<Socket> {
-        let sock = unsafe { err::cvt(ffi::srt_socket(af as int, c::SOCK_DGRAM, c::IPPROTO_UDP))? };
-        Ok(Socket(sock))
+    pub(crate) fn from_raw(sock: SRTSOCKET) -> Socket {
+        Socket { id: sock, observer: None, broken: Arc::new(AtomicBool::new(false)) }
     }
 
     pub fn as_raw(&self) -> SRTSOCKET {
-        self.0
+        self.id
+    }
+
+    /// Sets the observer notified about this socket, connections accepted
+    /// from it included.
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn Observer>>) {
+        self.observer = observer;
+    }
+
+    pub fn observer(&self) -> Option<&Arc<dyn Observer>> {
+        self.observer.as_ref()
     }
 
     pub fn connect(&self, addr: &SocketAddr) -> io::Result<()> {
+        if let Some(ref observer) = self.observer {
+            observer.connecting(self.id, addr);
+        }
         let (addrp, len) = into_sockaddr(addr);
-        unsafe {
-            err::cvt(ffi::srt_connect(self.0, addrp, len as int))?;
+        let res = unsafe {
+            err::cvt(ffi::srt_connect(self.id, addrp, len as int)).map(|_| ())
+        };
+        match res {
+            // a nonblocking connect is still in progress
+            Ok(()) if self.state() != SRT_SOCKSTATUS::SRTS_CONNECTED => {}
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            _ => self.connect_complete(addr, &res),
+        }
+        res
+    }
+
+    /// Reports the outcome of a nonblocking connect to the observer.
+    pub fn connect_complete(&self, addr: &SocketAddr, res: &io::Result<()>) {
+        if let Some(ref observer) = self.observer {
+            match *res {
+                Ok(()) => observer.connected(self.id, addr),
+                Err(ref e) => observer.connect_failed(self.id, addr, e),
+            }
         }
-        Ok(())
     }
 
     pub fn bind(&self, addr: &SocketAddr) -> io::Result<()> {
         let (addrp, len) = into_sockaddr(addr);
         unsafe {
-            err::cvt(ffi::srt_bind(self.0, addrp, len as int))?;
+            err::cvt(ffi::srt_bind(self.id, addrp, len as int))?;
         }
         Ok(())
     }
 
+    /// Binds the socket to an existing UDP socket, which is then owned and
+    /// eventually closed by SRT (or closed right away if binding fails).
+    #[cfg(unix)]
+    pub fn bind_acquire(&self, udp: UdpSocket) -> io::Result<()> {
+        use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+        let fd = udp.into_raw_fd();
+        err::cvt(unsafe { ffi::srt_bind_acquire(self.id, fd) }).map_err(|e| {
+            drop(unsafe { UdpSocket::from_raw_fd(fd) });
+            e
+        })?;
+        Ok(())
+    }
+
     pub fn listen(&self, backlog: usize) -> io::Result<()> {
         unsafe {
-            err::cvt(ffi::srt_listen(self.0, backlog as int))?;
+            err::cvt(ffi::srt_listen(self.id, backlog as int))?;
         }
         Ok(())
     }
@@ -64,50 +158,123 @@ impl Socket {
         let mut len = mem::size_of_val(&storage) as socklen_t;
         let sock = unsafe {
             err::cvt(ffi::srt_accept(
-                self.0,
+                self.id,
                 &mut storage as *mut _ as *mut _,
                 &mut len as *mut _ as *mut _,
             ))?
         };
         let addr = from_sockaddr(&storage, len)?;
-        Ok((Socket(sock), addr))
+        let accepted = Socket {
+            id: sock,
+            observer: self.observer.clone(),
+            broken: Arc::new(AtomicBool::new(false)),
+        };
+        if let Some(ref observer) = self.observer {
+            observer.accepted(self.id, sock, &addr);
+        }
+        Ok((accepted, addr))
     }
 
     pub fn peer_addr(&self) -> io::Result<SocketAddr> {
-        sockname(|buf, len| unsafe { ffi::srt_getpeername(self.0, buf, len as *mut _) })
+        sockname(|buf, len| unsafe { ffi::srt_getpeername(self.id, buf, len as *mut _) })
     }
 
     pub fn socket_addr(&self) -> io::Result<SocketAddr> {
-        sockname(|buf, len| unsafe { ffi::srt_getsockname(self.0, buf, len as *mut _) })
+        sockname(|buf, len| unsafe { ffi::srt_getsockname(self.id, buf, len as *mut _) })
     }
 
     pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
         let ret = err::cvt(unsafe {
-            ffi::srt_recvmsg(self.0, buf.as_mut_ptr() as *mut c_char, buf.len() as int)
-        })?;
+            ffi::srt_recvmsg(self.id, buf.as_mut_ptr() as *mut c_char, buf.len() as int)
+        }).map_err(|e| self.io_error(e, ffi::SRT_SOCKOPT::SRTO_RCVSYN))?;
+        if let Some(ref observer) = self.observer {
+            observer.received(self.id, ret as usize);
+        }
         Ok(ret as usize)
     }
 
-    pub fn recv_vectored(&self, _bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
-        Err(io::Error::new(io::ErrorKind::Other, "not implemented"))
+    /// Receives one message along with its metadata.
+    pub fn recv_msg(&self, buf: &mut [u8]) -> io::Result<(usize, MsgInfo)> {
+        let mut mctrl: ffi::SRT_MSGCTRL = unsafe { mem::zeroed() };
+        unsafe { ffi::srt_msgctrl_init(&mut mctrl) };
+        let ret = err::cvt(unsafe {
+            ffi::srt_recvmsg2(self.id, buf.as_mut_ptr() as *mut c_char, buf.len() as int, &mut mctrl)
+        }).map_err(|e| self.io_error(e, ffi::SRT_SOCKOPT::SRTO_RCVSYN))?;
+        if let Some(ref observer) = self.observer {
+            observer.received(self.id, ret as usize);
+        }
+        let info = MsgInfo {
+            msgno: mctrl.msgno,
+            pktseq: mctrl.pktseq,
+            src_time: mctrl.srctime,
+        };
+        Ok((ret as usize, info))
+    }
+
+    /// Receives one message and scatters it across `bufs`.
+    ///
+    /// SRT has no scattering receive, so unless only one of `bufs` is
+    /// non-empty the message goes through a temporary buffer first.
+    pub fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let mut nonempty = bufs.iter_mut().filter(|buf| !buf.is_empty());
+        match (nonempty.next(), nonempty.next()) {
+            (None, _) => return self.recv(&mut []),
+            (Some(buf), None) => return self.recv(buf),
+            _ => {}
+        }
+
+        let total = bufs.iter().map(|buf| buf.len()).sum();
+        let mut msg = vec![0u8; total];
+        let len = self.recv(&mut msg)?;
+
+        let mut rest = &msg[..len];
+        for buf in bufs.iter_mut() {
+            if rest.is_empty() {
+                break;
+            }
+            let n = cmp::min(buf.len(), rest.len());
+            buf[..n].copy_from_slice(&rest[..n]);
+            rest = &rest[n..];
+        }
+        Ok(len)
     }
 
     pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
         let ret = err::cvt(unsafe {
-            ffi::srt_sendmsg(self.0, buf.as_ptr() as *const c_char, buf.len() as int)
-        })?;
+            ffi::srt_sendmsg2(self.id, buf.as_ptr() as *const c_char, buf.len() as int, ptr::null_mut())
+        }).map_err(|e| self.io_error(e, ffi::SRT_SOCKOPT::SRTO_SNDSYN))?;
+        if let Some(ref observer) = self.observer {
+            observer.sent(self.id, ret as usize);
+        }
         Ok(ret as usize)
     }
 
-    pub fn send_vectored(&self, _bufs: &[IoSlice<'_>]) -> io::Result<usize> {
-        Err(io::Error::new(io::ErrorKind::Other, "not implemented"))
+    /// Gathers `bufs` into one message and sends it.
+    ///
+    /// Like `send`, this fails with `InvalidInput` if the message is larger
+    /// than SRT allows (the payload size in live mode), rather than
+    /// splitting it.
+    pub fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let mut nonempty = bufs.iter().filter(|buf| !buf.is_empty());
+        match (nonempty.next(), nonempty.next()) {
+            (None, _) => return self.send(&[]),
+            (Some(buf), None) => return self.send(buf),
+            _ => {}
+        }
+
+        let total = bufs.iter().map(|buf| buf.len()).sum();
+        let mut msg = Vec::with_capacity(total);
+        for buf in bufs {
+            msg.extend_from_slice(buf);
+        }
+        self.send(&msg)
     }
 
     pub fn set_recv_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         let mut blocking = (!nonblocking) as int;
         err::cvt(unsafe {
             ffi::srt_setsockopt(
-                self.0,
+                self.id,
                 0,
                 ffi::SRT_SOCKOPT::SRTO_RCVSYN,
                 &mut blocking as *mut _ as *mut _,
@@ -121,7 +288,7 @@ impl Socket {
         let mut blocking = (!nonblocking) as int;
         err::cvt(unsafe {
             ffi::srt_setsockopt(
-                self.0,
+                self.id,
                 0,
                 ffi::SRT_SOCKOPT::SRTO_SNDSYN,
                 &mut blocking as *mut _ as *mut _,
@@ -131,11 +298,31 @@ impl Socket {
         Ok(())
     }
 
+    /// Sets the timeout of blocking receive calls (`None` to block
+    /// indefinitely).
+    pub fn set_recv_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_RCVTIMEO, timeout_ms(timeout)?)
+    }
+
+    pub fn recv_timeout(&self) -> io::Result<Option<Duration>> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_RCVTIMEO).map(from_timeout_ms)
+    }
+
+    /// Sets the timeout of blocking send calls (`None` to block
+    /// indefinitely).
+    pub fn set_send_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_SNDTIMEO, timeout_ms(timeout)?)
+    }
+
+    pub fn send_timeout(&self) -> io::Result<Option<Duration>> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_SNDTIMEO).map(from_timeout_ms)
+    }
+
     pub fn set_sender(&self, sender: bool) -> io::Result<()> {
         let mut sender = sender as int;
         err::cvt(unsafe {
             ffi::srt_setsockflag(
-                self.0,
+                self.id,
                 ffi::SRT_SOCKOPT::SRTO_SENDER,
                 &mut sender as *mut _ as *mut _,
                 mem::size_of::<int>() as int,
@@ -148,7 +335,7 @@ impl Socket {
         let mut tsbpd_mode = tsbpd_mode as int;
         err::cvt(unsafe {
             ffi::srt_setsockopt(
-                self.0,
+                self.id,
                 0,
                 ffi::SRT_SOCKOPT::SRTO_TSBPDMODE,
                 &mut tsbpd_mode as *mut _ as *mut _,
@@ -162,7 +349,7 @@ impl Socket {
         let mut payload_size = payload_size as int;
         err::cvt(unsafe {
             ffi::srt_setsockopt(
-                self.0,
+                self.id,
                 0,
                 ffi::SRT_SOCKOPT::SRTO_PAYLOADSIZE,
                 &mut payload_size as *mut _ as *mut _,
@@ -172,11 +359,78 @@ impl Socket {
         Ok(())
     }
 
+    /// Maximum segment size, the largest UDP packet including the IP and
+    /// UDP headers.
+    pub fn set_mss(&self, mss: usize) -> io::Result<()> {
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_MSS, mss as int)
+    }
+
+    pub fn mss(&self) -> io::Result<usize> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_MSS).map(|v| v as usize)
+    }
+
+    /// Sets the IP time to live (IPv6 hop limit) of outgoing packets.
+    pub fn set_ip_ttl(&self, ttl: u8) -> io::Result<()> {
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_IPTTL, ttl as int)
+    }
+
+    pub fn ip_ttl(&self) -> io::Result<u8> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_IPTTL).map(|v| v as u8)
+    }
+
+    /// Sets the IP type of service (IPv6 traffic class) of outgoing
+    /// packets.
+    pub fn set_ip_tos(&self, tos: u8) -> io::Result<()> {
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_IPTOS, tos as int)
+    }
+
+    pub fn ip_tos(&self) -> io::Result<u8> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_IPTOS).map(|v| v as u8)
+    }
+
+    /// Returns the size of the sending buffer in bytes.
+    pub fn snd_buf(&self) -> io::Result<usize> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_SNDBUF).map(|v| v as usize)
+    }
+
+    /// Returns the state of the encryption key exchange.
+    pub fn km_state(&self) -> io::Result<SRT_KM_STATE> {
+        use crate::ffi::SRT_KM_STATE::*;
+        match self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_KMSTATE)? {
+            0 => Ok(SRT_KM_S_UNSECURED),
+            1 => Ok(SRT_KM_S_SECURING),
+            2 => Ok(SRT_KM_S_SECURED),
+            3 => Ok(SRT_KM_S_NOSECRET),
+            4 => Ok(SRT_KM_S_BADSECRET),
+            5 => Ok(SRT_KM_S_BADCRYPTOMODE),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown key material state")),
+        }
+    }
+
+    /// Returns the cipher mode (`SRT_CRYPTOMODE_*`, libsrt >= 1.5.2).
+    pub fn crypto_mode(&self) -> io::Result<int> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_CRYPTOMODE)
+    }
+
+    pub fn payload_size(&self) -> io::Result<usize> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_PAYLOADSIZE).map(|v| v as usize)
+    }
+
+    /// Whether data is sent in messages with boundaries (file mode only,
+    /// live mode always uses messages).
+    pub fn set_message_api(&self, message_api: bool) -> io::Result<()> {
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_MESSAGEAPI, message_api as int)
+    }
+
+    pub fn message_api(&self) -> io::Result<bool> {
+        self.getsockopt_bool(ffi::SRT_SOCKOPT::SRTO_MESSAGEAPI)
+    }
+
     pub fn set_trans_type(&self, trans_type: SRT_TRANSTYPE) -> io::Result<()> {
         let mut trans_type = trans_type as int;
         err::cvt(unsafe {
             ffi::srt_setsockopt(
-                self.0,
+                self.id,
                 0,
                 ffi::SRT_SOCKOPT::SRTO_TRANSTYPE,
                 &mut trans_type as *mut _ as *mut _,
@@ -186,21 +440,168 @@ impl Socket {
         Ok(())
     }
 
+    pub fn set_reuse_addr(&self, reuse_addr: bool) -> io::Result<()> {
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_REUSEADDR, reuse_addr as int)
+    }
+
+    pub fn reuse_addr(&self) -> io::Result<bool> {
+        self.getsockopt_bool(ffi::SRT_SOCKOPT::SRTO_REUSEADDR)
+    }
+
+    /// Allows a listener to accept group connections.
+    pub fn set_group_connect(&self, group_connect: bool) -> io::Result<()> {
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_GROUPCONNECT, group_connect as int)
+    }
+
+    /// Whether an IPv6 socket accepts IPv6 traffic only (has to be set
+    /// before binding to the IPv6 wildcard address).
+    pub fn set_ipv6_only(&self, ipv6_only: bool) -> io::Result<()> {
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_IPV6ONLY, ipv6_only as int)
+    }
+
+    /// Sets the passphrase used to derive the encryption key (10..79
+    /// characters, empty to disable encryption).
+    pub fn set_passphrase(&self, passphrase: &str) -> io::Result<()> {
+        self.setsockopt_str(ffi::SRT_SOCKOPT::SRTO_PASSPHRASE, passphrase)
+    }
+
+    /// Sets the receiver (TSBPD) delay of both directions.
+    pub fn set_latency(&self, latency: Duration) -> io::Result<()> {
+        let ms = cmp::min(latency.as_millis(), int::max_value() as u128);
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_LATENCY, ms as int)
+    }
+
+    /// Returns the receiver delay (the larger of both peers' settings once
+    /// connected).
+    pub fn latency(&self) -> io::Result<Duration> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_RCVLATENCY)
+            .map(|ms| Duration::from_millis(ms as u64))
+    }
+
+    /// Sets how long connecting waits for the handshake to complete.
+    pub fn set_connect_timeout(&self, timeout: Duration) -> io::Result<()> {
+        let ms = cmp::min(timeout.as_millis(), int::max_value() as u128);
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_CONNTIMEO, ms as int)
+    }
+
+    /// Selects the congestion controller ("live" or "file").
+    pub fn set_congestion(&self, congestion: &str) -> io::Result<()> {
+        self.setsockopt_str(ffi::SRT_SOCKOPT::SRTO_CONGESTION, congestion)
+    }
+
+    pub fn congestion(&self) -> io::Result<String> {
+        self.getsockopt_str(ffi::SRT_SOCKOPT::SRTO_CONGESTION)
+    }
+
+    pub fn set_packet_filter(&self, filter: &str) -> io::Result<()> {
+        self.setsockopt_str(ffi::SRT_SOCKOPT::SRTO_PACKETFILTER, filter)
+    }
+
+    pub fn packet_filter(&self) -> io::Result<String> {
+        self.getsockopt_str(ffi::SRT_SOCKOPT::SRTO_PACKETFILTER)
+    }
+
+    /// Returns the SRT version of this side of the connection.
+    pub fn version(&self) -> io::Result<Version> {
+        self.getsockopt::<u32>(ffi::SRT_SOCKOPT::SRTO_VERSION).map(Version::from_raw)
+    }
+
+    /// Returns the SRT version of the peer, reported in the handshake.
+    pub fn peer_version(&self) -> io::Result<Version> {
+        self.getsockopt::<u32>(ffi::SRT_SOCKOPT::SRTO_PEERVERSION).map(Version::from_raw)
+    }
+
+    /// Rejects peers older than `version` during the handshake.
+    pub fn set_min_version(&self, version: Version) -> io::Result<()> {
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_MINVERSION, version.to_raw())
+    }
+
+    pub fn min_version(&self) -> io::Result<Version> {
+        self.getsockopt::<u32>(ffi::SRT_SOCKOPT::SRTO_MINVERSION).map(Version::from_raw)
+    }
+
+    /// Returns why the connection was rejected (`SRT_REJ_*`, or a code
+    /// of 1000 and above set by the peer).
+    pub fn reject_reason(&self) -> int {
+        unsafe { ffi::srt_getrejectreason(self.id) }
+    }
+
+    /// How long closing waits for unsent data (`None` to close at once).
+    ///
+    /// The timeout is kept in whole seconds, fractions are rounded up so a
+    /// short linger doesn't turn into none at all.
+    pub fn set_linger(&self, linger: Option<Duration>) -> io::Result<()> {
+        let secs = linger.map(|d| d.as_secs() + (d.subsec_nanos() > 0) as u64);
+        let linger = c::linger {
+            l_onoff: linger.is_some() as int,
+            l_linger: secs.map(|s| cmp::min(s, int::max_value() as u64) as int).unwrap_or(0),
+        };
+        self.setsockopt(ffi::SRT_SOCKOPT::SRTO_LINGER, linger)
+    }
+
+    pub fn linger(&self) -> io::Result<Option<Duration>> {
+        let linger = self.getsockopt::<c::linger>(ffi::SRT_SOCKOPT::SRTO_LINGER)?;
+        if linger.l_onoff != 0 {
+            Ok(Some(Duration::from_secs(linger.l_linger as u64)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the number of packets in the sending buffer which are not
+    /// yet acknowledged by the peer.
+    pub fn snd_data(&self) -> io::Result<usize> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_SNDDATA).map(|v| v as usize)
+    }
+
+    /// Returns the number of packets and bytes in the sending buffer.
+    pub fn snd_buffer(&self) -> io::Result<(usize, usize)> {
+        sndbuffer(self.id)
+    }
+
+    /// Returns the number of packets available for reading.
+    pub fn rcv_data(&self) -> io::Result<usize> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_RCVDATA).map(|v| v as usize)
+    }
+
+    /// Returns the size of the receiving buffer in bytes.
+    pub fn rcv_buf(&self) -> io::Result<usize> {
+        self.getsockopt::<int>(ffi::SRT_SOCKOPT::SRTO_RCVBUF).map(|v| v as usize)
+    }
+
+    /// Closes the socket, reporting the error `Drop` would ignore.
+    pub fn close(mut self) -> io::Result<()> {
+        let sock = self.id;
+        self.check_broken();
+        let observer = self.observer.take();
+        mem::forget(self);
+        let res = err::cvt(unsafe { ffi::srt_close(sock) });
+        if let Some(observer) = observer {
+            observer.closed(sock);
+        }
+        res?;
+        Ok(())
+    }
+
+    pub fn state(&self) -> SRT_SOCKSTATUS {
+        sockstate(self.id)
+    }
+
     pub fn is_broken(&self) -> io::Result<bool> {
         Ok(unsafe {
-            ffi::srt_getsockstate(self.0) == ffi::SRT_SOCKSTATUS::SRTS_BROKEN
+            ffi::srt_getsockstate(self.id) == ffi::SRT_SOCKSTATUS::SRTS_BROKEN
         })
     }
 
     pub fn is_closing(&self) -> io::Result<bool> {
         Ok(unsafe {
-            ffi::srt_getsockstate(self.0) == ffi::SRT_SOCKSTATUS::SRTS_CLOSING
+            ffi::srt_getsockstate(self.id) == ffi::SRT_SOCKSTATUS::SRTS_CLOSING
         })
     }
 
     pub fn is_closed(&self) -> io::Result<bool> {
         Ok(unsafe {
-            ffi::srt_getsockstate(self.0) == ffi::SRT_SOCKSTATUS::SRTS_CLOSED
+            ffi::srt_getsockstate(self.id) == ffi::SRT_SOCKSTATUS::SRTS_CLOSED
         })
     }
 
@@ -214,12 +615,201 @@ impl Socket {
         let err = err::Error::new(errcode, errstr);
         Ok(Some(io::Error::new(err.kind(), err)))
     }
+
+    /// Tells a blocking call timing out from a nonblocking one that would
+    /// block, and a broken connection from other failures.
+    ///
+    /// A blocking call can only fail to transfer data because its timeout
+    /// expired, whatever "again" error libsrt reports for it.
+    fn io_error(&self, e: io::Error, syn: ffi::SRT_SOCKOPT) -> io::Error {
+        if e.kind() == io::ErrorKind::WouldBlock {
+            if let Ok(true) = self.getsockopt_bool(syn) {
+                return io::Error::new(io::ErrorKind::TimedOut, e);
+            }
+            return e;
+        }
+        self.check_broken();
+        e
+    }
+
+    /// Tells the observer once if the connection is broken.
+    fn check_broken(&self) {
+        if let Some(ref observer) = self.observer {
+            if self.state() == SRT_SOCKSTATUS::SRTS_BROKEN {
+                report_broken(&**observer, &self.broken, self.id);
+            }
+        }
+    }
+
+    /// The observer and broken flag a `Poll` reports an error event to,
+    /// the latter only as long as the socket lives.
+    pub(crate) fn watcher(&self) -> Option<(Arc<dyn Observer>, Weak<AtomicBool>)> {
+        let observer = self.observer.clone()?;
+        Some((observer, Arc::downgrade(&self.broken)))
+    }
+
+    fn setsockopt<T: Copy>(&self, opt: ffi::SRT_SOCKOPT, val: T) -> io::Result<()> {
+        err::cvt(unsafe {
+            ffi::srt_setsockopt(
+                self.id,
+                0,
+                opt,
+                &val as *const _ as *const _,
+                mem::size_of::<T>() as int,
+            )
+        })?;
+        Ok(())
+    }
+
+    fn setsockopt_str(&self, opt: ffi::SRT_SOCKOPT, val: &str) -> io::Result<()> {
+        err::cvt(unsafe {
+            ffi::srt_setsockopt(
+                self.id,
+                0,
+                opt,
+                val.as_ptr() as *const _,
+                val.len() as int,
+            )
+        })?;
+        Ok(())
+    }
+
+    fn getsockopt_str(&self, opt: ffi::SRT_SOCKOPT) -> io::Result<String> {
+        let mut buf = [0u8; 512];
+        let mut len = buf.len() as int;
+        err::cvt(unsafe {
+            ffi::srt_getsockopt(
+                self.id,
+                0,
+                opt,
+                buf.as_mut_ptr() as *mut _,
+                &mut len,
+            )
+        })?;
+        let val = &buf[..len as usize];
+        let val = val.split(|&b| b == 0).next().unwrap_or(val);
+        Ok(String::from_utf8_lossy(val).into_owned())
+    }
+
+    // Boolean options are set from an int but read back as a one byte C++
+    // `bool`; reading them into a zeroed int happened to work, this doesn't
+    // depend on the byte order.
+    fn getsockopt_bool(&self, opt: ffi::SRT_SOCKOPT) -> io::Result<bool> {
+        self.getsockopt::<u8>(opt).map(|v| v != 0)
+    }
+
+    fn getsockopt<T: Copy>(&self, opt: ffi::SRT_SOCKOPT) -> io::Result<T> {
+        let mut val: T = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<T>() as int;
+        err::cvt(unsafe {
+            ffi::srt_getsockopt(
+                self.id,
+                0,
+                opt,
+                &mut val as *mut _ as *mut _,
+                &mut len,
+            )
+        })?;
+        Ok(val)
+    }
+}
+
+fn timeout_ms(timeout: Option<Duration>) -> io::Result<int> {
+    match timeout {
+        None => Ok(-1),
+        Some(timeout) if timeout.as_secs() == 0 && timeout.subsec_nanos() == 0 => {
+            Err(io::Error::new(io::ErrorKind::InvalidInput,
+                               "cannot set a 0 duration timeout"))
+        }
+        Some(timeout) => {
+            let ms = timeout.as_millis();
+            if ms > int::max_value() as u128 {
+                Ok(int::max_value())
+            } else {
+                // round sub-millisecond timeouts up instead of disabling them
+                Ok(cmp::max(ms as int, 1))
+            }
+        }
+    }
+}
+
+fn from_timeout_ms(ms: int) -> Option<Duration> {
+    if ms < 0 {
+        None
+    } else {
+        Some(Duration::from_millis(ms as u64))
+    }
+}
+
+/// Returns the state of a socket by its id (`SRTS_NONEXIST` once it has
+/// been closed and released).
+pub fn sockstate(sock: SRTSOCKET) -> SRT_SOCKSTATUS {
+    unsafe { ffi::srt_getsockstate(sock) }
+}
+
+/// Metadata of a received message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MsgInfo {
+    msgno: i32,
+    pktseq: i32,
+    src_time: i64,
+}
+
+impl MsgInfo {
+    /// Message number, increasing with every message the peer sent
+    pub fn msgno(&self) -> i32 {
+        self.msgno
+    }
+
+    /// Sequence number of the first packet of the message
+    pub fn pktseq(&self) -> i32 {
+        self.pktseq
+    }
+
+    /// Time the peer sent the message at, in microseconds of the local
+    /// SRT clock
+    pub fn src_time(&self) -> i64 {
+        self.src_time
+    }
+}
+
+/// Returns the description libsrt gives for a rejection reason.
+pub fn rejectreason_str(reason: int) -> String {
+    unsafe { CStr::from_ptr(ffi::srt_rejectreason_str(reason)) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Returns the number of packets and bytes in the sending buffer of a
+/// socket by its id.
+pub fn sndbuffer(sock: SRTSOCKET) -> io::Result<(usize, usize)> {
+    let mut blocks: usize = 0;
+    let mut bytes: usize = 0;
+    err::cvt(unsafe { ffi::srt_getsndbuffer(sock, &mut blocks, &mut bytes) })?;
+    Ok((blocks, bytes))
+}
+
+impl fmt::Debug for Socket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Socket").field(&self.id).finish()
+    }
+}
+
+/// Calls `Observer::broken` unless the socket or a poll already did.
+pub(crate) fn report_broken(observer: &dyn Observer, broken: &AtomicBool, sock: SRTSOCKET) {
+    if !broken.swap(true, Ordering::SeqCst) {
+        observer.broken(sock);
+    }
 }
 
 impl Drop for Socket {
     fn drop(&mut self) {
+        self.check_broken();
         unsafe {
-            ffi::srt_close(self.0);
+            ffi::srt_close(self.id);
+        }
+        if let Some(ref observer) = self.observer {
+            observer.closed(self.id);
         }
     }
 }
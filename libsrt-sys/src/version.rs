@@ -0,0 +1,147 @@
+use std::{
+    fmt,
+    io,
+    str::FromStr,
+};
+
+use crate::ffi;
+use crate::socket::Socket;
+
+/// A SRT version number (`major.minor.patch`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl Version {
+    pub const fn new(major: u8, minor: u8, patch: u8) -> Version {
+        Version {
+            major: major,
+            minor: minor,
+            patch: patch,
+        }
+    }
+
+    /// Decodes the `0x00XXYYZZ` form used by libsrt.
+    pub fn from_raw(raw: u32) -> Version {
+        Version::new((raw >> 16) as u8, (raw >> 8) as u8, raw as u8)
+    }
+
+    /// Encodes the version in the `0x00XXYYZZ` form used by libsrt.
+    pub fn to_raw(&self) -> u32 {
+        (self.major as u32) << 16 | (self.minor as u32) << 8 | self.patch as u32
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for Version {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Version> {
+        let invalid = || {
+            io::Error::new(io::ErrorKind::InvalidInput, "invalid SRT version")
+        };
+        let mut nums = s.trim().splitn(3, '.').map(|n| n.parse::<u8>());
+        let major = nums.next().ok_or_else(invalid)?.map_err(|_| invalid())?;
+        let minor = nums.next().ok_or_else(invalid)?.map_err(|_| invalid())?;
+        let patch = match nums.next() {
+            Some(patch) => patch.map_err(|_| invalid())?,
+            None => 0,
+        };
+        Ok(Version::new(major, minor, patch))
+    }
+}
+
+/// Returns the version of the linked libsrt.
+pub fn version() -> Version {
+    Version::from_raw(unsafe { ffi::srt_getversion() })
+}
+
+/// Optional features of the linked libsrt.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    encryption: bool,
+    groups: bool,
+    packet_filter: bool,
+}
+
+impl Capabilities {
+    /// Returns true if libsrt is built with encryption (`SRTO_PASSPHRASE`).
+    pub fn encryption(&self) -> bool {
+        self.encryption
+    }
+
+    /// Returns true if libsrt is built with socket groups (bonding); they
+    /// can only be used with the `bonding` feature of this crate though.
+    pub fn groups(&self) -> bool {
+        self.groups
+    }
+
+    /// Returns true if packet filters (FEC) are available.
+    pub fn packet_filter(&self) -> bool {
+        self.packet_filter
+    }
+}
+
+/// Probes the linked libsrt for optional features.
+///
+/// Each feature is tried on a temporary socket, so this is not meant to be
+/// called in a hot path.
+pub fn capabilities() -> io::Result<Capabilities> {
+    crate::try_init()?;
+
+    let sock = Socket::new()?;
+    let encryption = sock.set_passphrase("capabilities").is_ok();
+    // the builtin FEC filter refuses a configuration without cols
+    let packet_filter = sock.set_packet_filter("fec,cols:10,rows:1").is_ok();
+
+    Ok(Capabilities {
+        encryption: encryption,
+        groups: groups_available(),
+        packet_filter: packet_filter,
+    })
+}
+
+fn groups_available() -> bool {
+    let grp = unsafe { ffi::srt_create_group(ffi::SRT_GROUP_TYPE::SRT_GTYPE_BROADCAST) };
+    if grp == ffi::SRT_INVALID_SOCK {
+        return false;
+    }
+    unsafe { ffi::srt_close(grp) };
+    true
+}
+
+#[cfg(test)]
+mod version_tests {
+    use super::*;
+
+    #[test]
+    fn raw_version() {
+        let version = Version::from_raw(0x0001_0503);
+        assert_eq!(version, Version::new(1, 5, 3));
+        assert_eq!(version.to_raw(), 0x0001_0503);
+        assert_eq!(version.to_string(), "1.5.3");
+    }
+
+    #[test]
+    fn parse_version() {
+        assert_eq!("1.4.2".parse::<Version>().unwrap(), Version::new(1, 4, 2));
+        assert_eq!("1.5".parse::<Version>().unwrap(), Version::new(1, 5, 0));
+        assert!("1".parse::<Version>().is_err());
+        assert!("1.x.0".parse::<Version>().is_err());
+        assert!(Version::new(1, 3, 4) < Version::new(1, 4, 0));
+    }
+
+    #[test]
+    fn bundled_capabilities() {
+        // the bundled libsrt always builds the FEC filter
+        assert!(capabilities().unwrap().packet_filter());
+    }
+}
@@ -1,6 +1,6 @@
 use libsrt_sys::{EventKind, Events, Poll, Socket, Token};
 use std::{
-    io,
+    io::{self, IoSlice, IoSliceMut},
     str,
     time::Duration,
     thread,
@@ -13,14 +13,14 @@ fn sync_echo() {
     let try_addr = "127.0.0.1:0".parse().unwrap();
 
     // prepare server
-    let server_sock = Socket::new(&try_addr).unwrap();
+    let server_sock = Socket::new().unwrap();
     server_sock.bind(&try_addr).unwrap();
     server_sock.listen(1).unwrap();
 
     let addr = server_sock.socket_addr().unwrap();
 
     // prepare client
-    let client_sock = Socket::new(&addr).unwrap();
+    let client_sock = Socket::new().unwrap();
 
     let server_thread = thread::spawn(move || {
         let (peer_sock, _peer_addr) = server_sock.accept().unwrap();
@@ -50,7 +50,7 @@ fn async_echo() {
     let try_addr = "127.0.0.1:0".parse().unwrap();
 
     // prepare server
-    let server_sock = Socket::new(&try_addr).unwrap();
+    let server_sock = Socket::new().unwrap();
     server_sock.set_recv_nonblocking(true).unwrap();
     server_sock.set_send_nonblocking(true).unwrap();
 
@@ -66,7 +66,7 @@ fn async_echo() {
     let addr = server_sock.socket_addr().unwrap();
 
     // prepare client
-    let client_sock = Socket::new(&addr).unwrap();
+    let client_sock = Socket::new().unwrap();
     client_sock.set_recv_nonblocking(true).unwrap();
     client_sock.set_send_nonblocking(true).unwrap();
 
@@ -203,3 +203,93 @@ fn async_echo() {
 
     server_thread.join().unwrap();
 }
+
+#[test]
+fn vectored_echo() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    // prepare server
+    let server_sock = Socket::new().unwrap();
+    server_sock.bind(&try_addr).unwrap();
+    server_sock.listen(1).unwrap();
+
+    let addr = server_sock.socket_addr().unwrap();
+
+    // prepare client
+    let client_sock = Socket::new().unwrap();
+
+    let server_thread = thread::spawn(move || {
+        let (peer_sock, _peer_addr) = server_sock.accept().unwrap();
+
+        // one message scattered across the slices
+        let mut head = [0; 5];
+        let mut mid = [0; 4];
+        let mut tail = [0; 2048];
+        let nread = {
+            let mut bufs = [
+                IoSliceMut::new(&mut head),
+                IoSliceMut::new(&mut mid),
+                IoSliceMut::new(&mut tail),
+            ];
+            peer_sock.recv_vectored(&mut bufs).unwrap()
+        };
+        assert_eq!(nread, 16);
+        assert_eq!(b"hello", &head);
+        assert_eq!(b" lib", &mid);
+        assert_eq!(b"srt-sys", &tail[..7]);
+
+        // the oversized message never made it, the next one follows
+        let mut buf = [0; 2048];
+        let nread = peer_sock.recv(&mut buf).unwrap();
+        assert_eq!(nread, 1316);
+        assert!(buf[..1000].iter().all(|&b| b == 1));
+        assert!(buf[1000..1316].iter().all(|&b| b == 2));
+
+        thread::sleep(Duration::from_millis(500)); // XXX
+    });
+
+    client_sock.connect(&addr).unwrap();
+
+    let bufs = [
+        IoSlice::new(b"hello libsrt"),
+        IoSlice::new(b""),
+        IoSlice::new(b"-sys"),
+    ];
+    assert_eq!(client_sock.send_vectored(&bufs).unwrap(), MESSAGE.len());
+
+    // larger than the payload size, so it is refused rather than split
+    let bufs = [IoSlice::new(&[1; 1000]), IoSlice::new(&[2; 1000])];
+    assert_eq!(client_sock.send_vectored(&bufs).unwrap_err().kind(),
+               io::ErrorKind::InvalidInput);
+    let bufs = [IoSlice::new(&[1; 1000]), IoSlice::new(&[2; 316])];
+    assert_eq!(client_sock.send_vectored(&bufs).unwrap(), 1316);
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn bool_options() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+    let sock = Socket::new().unwrap();
+
+    for &value in &[true, false, true] {
+        sock.set_message_api(value).unwrap();
+        assert_eq!(sock.message_api().unwrap(), value);
+        sock.set_reuse_addr(value).unwrap();
+        assert_eq!(sock.reuse_addr().unwrap(), value);
+    }
+
+    // RCVSYN decides whether an empty receive timed out or would block
+    let server_sock = Socket::new().unwrap();
+    server_sock.bind(&try_addr).unwrap();
+    server_sock.listen(1).unwrap();
+    let client_sock = Socket::new().unwrap();
+    client_sock.connect(&server_sock.socket_addr().unwrap()).unwrap();
+    let (_peer_sock, _peer_addr) = server_sock.accept().unwrap();
+
+    let mut buf = [0; 2048];
+    client_sock.set_recv_timeout(Some(Duration::from_millis(10))).unwrap();
+    assert_eq!(client_sock.recv(&mut buf).unwrap_err().kind(), io::ErrorKind::TimedOut);
+    client_sock.set_recv_nonblocking(true).unwrap();
+    assert_eq!(client_sock.recv(&mut buf).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+}
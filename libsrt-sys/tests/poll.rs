@@ -14,8 +14,7 @@ fn wait_no_sockets_in_poll() {
 
 #[test]
 fn wait_empty_call() {
-    let addr = "127.0.0.1:0".parse().unwrap();
-    let sock = Socket::new(&addr).unwrap();
+    let sock = Socket::new().unwrap();
     sock.set_recv_nonblocking(true).unwrap();
     sock.set_send_nonblocking(true).unwrap();
 
@@ -32,8 +31,7 @@ fn wait_empty_call() {
 
 #[test]
 fn wait_all_sockets_in_poll_released() {
-    let addr = "127.0.0.1:0".parse().unwrap();
-    let sock = Socket::new(&addr).unwrap();
+    let sock = Socket::new().unwrap();
     sock.set_recv_nonblocking(true).unwrap();
     sock.set_send_nonblocking(true).unwrap();
     sock.set_sender(true).unwrap();
@@ -56,7 +54,7 @@ fn notify_connection_break() {
     let try_addr = "127.0.0.1:0".parse().unwrap();
 
     // prepare server
-    let server_sock = Socket::new(&try_addr).unwrap();
+    let server_sock = Socket::new().unwrap();
     server_sock.set_recv_nonblocking(true).unwrap();
     server_sock.set_send_nonblocking(true).unwrap();
 
@@ -72,7 +70,7 @@ fn notify_connection_break() {
     let addr = server_sock.socket_addr().unwrap();
 
     // prepare client
-    let client_sock = Socket::new(&addr).unwrap();
+    let client_sock = Socket::new().unwrap();
     client_sock.set_recv_nonblocking(true).unwrap();
     client_sock.set_send_nonblocking(true).unwrap();
 
@@ -1,15 +1,39 @@
 use std::{
+    collections::HashMap,
     fmt,
     io::{self, IoSlice, IoSliceMut, Read, Write},
-    net::SocketAddr,
-    time::Duration,
+    cmp,
+    net::{SocketAddr, UdpSocket},
+    slice::Chunks,
+    str::FromStr,
+    panic,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+        Arc,
+        Condvar,
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
+#[cfg(feature = "stream")]
+use std::task::{self, Context};
 
-use libsrt_sys::{self as sys, Socket, LIVE_DEF_PLSIZE};
+use libsrt_sys::{self as sys, Socket, KM_STATE, SOCKSTATUS, SRTSOCKET};
 pub use libsrt_sys::{
     int,
+    MsgInfo,
+    Observer,
     TRANSTYPE,
-    EventKind, Events, Token
+    Version,
+    Event, EventKind, Events, Token
+};
+#[cfg(feature = "bonding")]
+pub use libsrt_sys::{
+    GROUPTYPE,
+    MEMBERSTATUS,
+    Endpoint, Member,
 };
 
 pub trait AsSocket {
@@ -19,6 +43,11 @@ pub trait AsSocket {
     fn take_error(&self) -> io::Result<Option<io::Error>> {
         self.as_socket().take_error()
     }
+
+    /// Returns the current state of the socket.
+    fn state(&self) -> SocketState {
+        self.as_socket().state().into()
+    }
 }
 
 pub trait Bind: AsSocket {
@@ -33,6 +62,32 @@ pub trait Connect: Bind {
     fn peer_addr(&self) -> io::Result<SocketAddr> {
         self.as_socket().peer_addr()
     }
+
+    /// Sets the read timeout of a blocking connection (`None` to block
+    /// indefinitely). An expired timeout fails the read with `TimedOut`.
+    ///
+    /// A zero `Duration` is rejected with `InvalidInput`.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.as_socket().set_recv_timeout(timeout)
+    }
+
+    /// Sets the write timeout of a blocking connection (`None` to block
+    /// indefinitely). An expired timeout fails the write with `TimedOut`.
+    ///
+    /// A zero `Duration` is rejected with `InvalidInput`.
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.as_socket().set_send_timeout(timeout)
+    }
+
+    /// Returns the read timeout (`None` if reads block indefinitely).
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.as_socket().recv_timeout()
+    }
+
+    /// Returns the write timeout (`None` if writes block indefinitely).
+    fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        self.as_socket().send_timeout()
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -40,10 +95,27 @@ pub trait Connect: Bind {
 ////////////////////////////////////////////////////////////////////////////////
 
 /// Builder struct for a SRT instance
+#[derive(Clone)]
 pub struct Builder {
     nonblocking: bool,
-    payload_size: usize,
+    payload_size: Option<usize>,
     trans_type: TRANSTYPE,
+    message_api: Option<bool>,
+    local_addr: Option<SocketAddr>,
+    backlog: usize,
+    reuse_addr: Option<bool>,
+    linger: Option<Option<Duration>>,
+    congestion: Option<Congestion>,
+    fec: Option<Fec>,
+    ip_ttl: Option<u8>,
+    ip_tos: Option<u8>,
+    min_peer_version: Option<Version>,
+    latency: Option<Duration>,
+    passphrase: Option<String>,
+    connect_timeout: Option<Duration>,
+    observer: Option<Arc<dyn Observer>>,
+    #[cfg(feature = "bonding")]
+    group_connect: bool,
 }
 
 impl Builder {
@@ -51,7 +123,23 @@ impl Builder {
         Builder {
             nonblocking: false,
             trans_type: TRANSTYPE::SRTT_LIVE,
-            payload_size: LIVE_DEF_PLSIZE,
+            payload_size: None,
+            message_api: None,
+            local_addr: None,
+            backlog: 128,
+            reuse_addr: None,
+            linger: None,
+            congestion: None,
+            fec: None,
+            ip_ttl: None,
+            ip_tos: None,
+            min_peer_version: None,
+            latency: None,
+            passphrase: None,
+            connect_timeout: None,
+            observer: None,
+            #[cfg(feature = "bonding")]
+            group_connect: false,
         }
     }
 
@@ -62,52 +150,253 @@ impl Builder {
     }
 
     /// Maximum payload size sent in one UDP packet (0 if unlimited)
+    ///
+    /// Defaults to the `trans_type` preset's size (`LIVE_DEF_PLSIZE` in
+    /// live mode, unlimited in file mode).
     pub fn payload_size(mut self, payload_size: usize) -> Self {
-        self.payload_size = payload_size;
+        self.payload_size = Some(payload_size);
+        self
+    }
+
+    /// Whether data is sent in messages with boundaries (see
+    /// `Stream::into_message_stream`) or as a byte stream
+    /// (see `Stream::into_byte_stream`)
+    ///
+    /// Only file mode supports a byte stream, live mode is always message
+    /// based.
+    pub fn message_api(mut self, message_api: bool) -> Self {
+        self.message_api = Some(message_api);
         self
     }
 
     /// Transmission type (set of options required for given transmission type)
+    ///
+    /// The preset is applied first, so options set explicitly on the
+    /// builder (e.g. `congestion`) take precedence over it.
     pub fn trans_type(mut self, trans_type: TRANSTYPE) -> Self {
         self.trans_type = trans_type;
         self
     }
 
+    /// Congestion controller (the `trans_type` preset's one if not set)
+    pub fn congestion(mut self, congestion: Congestion) -> Self {
+        self.congestion = Some(congestion);
+        self
+    }
+
+    /// Local address the connecting socket is bound to before connecting
+    /// (ephemeral port on any interface if not set)
+    pub fn local_addr(mut self, addr: SocketAddr) -> Self {
+        self.local_addr = Some(addr);
+        self
+    }
+
+    /// Maximum number of connections waiting to be accepted by a listener
+    /// (128 by default)
+    pub fn backlog(mut self, backlog: usize) -> Self {
+        self.backlog = backlog;
+        self
+    }
+
+    /// Whether the local address may be shared with other SRT sockets
+    pub fn reuse_addr(mut self, reuse_addr: bool) -> Self {
+        self.reuse_addr = Some(reuse_addr);
+        self
+    }
+
+    /// How long closing a blocking socket waits for unsent data to be
+    /// delivered, in whole seconds (`None` to drop it at once)
+    ///
+    /// Fractions of a second are rounded up, e.g. 1.5s lingers for 2s.
+    ///
+    /// The default depends on the `trans_type`: off in live mode,
+    /// 180 seconds in file mode.
+    pub fn linger(mut self, linger: Option<Duration>) -> Self {
+        self.linger = Some(linger);
+        self
+    }
+
+    /// Forward error correction (`SRTO_PACKETFILTER`); the peer has to
+    /// agree on a compatible configuration
+    pub fn fec(mut self, fec: Fec) -> Self {
+        self.fec = Some(fec);
+        self
+    }
+
+    /// IP time to live (IPv6 hop limit) of outgoing packets
+    pub fn ip_ttl(mut self, ttl: u8) -> Self {
+        self.ip_ttl = Some(ttl);
+        self
+    }
+
+    /// Raw IP type of service byte (IPv6 traffic class) of outgoing
+    /// packets, see `dscp` for the common case
+    pub fn ip_tos(mut self, tos: u8) -> Self {
+        self.ip_tos = Some(tos);
+        self
+    }
+
+    /// DSCP class outgoing packets are marked with (replaces the upper six
+    /// bits of `ip_tos`, leaving the ECN bits)
+    pub fn dscp(mut self, dscp: Dscp) -> Self {
+        self.ip_tos = Some(dscp.to_tos(self.ip_tos.unwrap_or(0)));
+        self
+    }
+
+    /// Receiver buffering delay absorbing retransmissions (the larger of
+    /// both peers' values is used, 120ms by default in live mode)
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Passphrase the encryption key is derived from (10 to 79
+    /// characters); the peer has to use the same one
+    pub fn passphrase<S: Into<String>>(mut self, passphrase: S) -> Self {
+        self.passphrase = Some(passphrase.into());
+        self
+    }
+
+    /// How long connecting waits for the handshake to complete before
+    /// failing with `TimedOut` (3 seconds by default)
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Oldest SRT version a peer may run, older ones are rejected during
+    /// the handshake with `RejectReason::Version`
+    pub fn min_peer_version(mut self, version: Version) -> Self {
+        self.min_peer_version = Some(version);
+        self
+    }
+
+    /// Observer notified about the connections opened by this builder and
+    /// accepted by its listeners (group connections are not observed)
+    pub fn observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Whether listeners accept group connections (see `Stream::into_group`)
+    #[cfg(feature = "bonding")]
+    pub fn group_connect(mut self, group_connect: bool) -> Self {
+        self.group_connect = group_connect;
+        self
+    }
+
     /// Opens a SRT connection to a remote host.
     pub fn connect(&self, addr: &SocketAddr) -> io::Result<Stream> {
-        sys::init();
+        sys::try_init()?;
 
-        let sock = Socket::new(addr)?;
+        let mut sock = Socket::new()?;
+        sock.set_observer(self.observer.clone());
+        self.configure(&sock)?;
+        if let Some(ref local_addr) = self.local_addr {
+            sock.bind(local_addr)?;
+        }
 
-        sock.set_trans_type(self.trans_type)?;
-        sock.set_payload_size(self.payload_size)?;
+        self.connect_socket(sock, addr)
+    }
+
+    /// Opens a SRT connection to a remote host over an existing UDP socket.
+    ///
+    /// The UDP socket is owned by SRT from now on and closed together with
+    /// the returned `Stream`.
+    pub fn connect_from(
+        &self,
+        udp: UdpSocket,
+        addr: &SocketAddr,
+    ) -> io::Result<Stream> {
+        sys::try_init()?;
+
+        let mut sock = Socket::new()?;
+        sock.set_observer(self.observer.clone());
+        self.configure(&sock)?;
+        sock.bind_acquire(udp)?;
+
+        self.connect_socket(sock, addr)
+    }
 
+    fn connect_socket(&self, sock: Socket, addr: &SocketAddr) -> io::Result<Stream> {
         if self.nonblocking {
             sock.set_send_nonblocking(true)?;
             sock.set_recv_nonblocking(true)?;
             match sock.connect(addr) {
                 Ok(_) => {}
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-                Err(e) => return Err(e),
+                Err(e) => return Err(rejected(&sock, e)),
             }
         } else {
-            sock.connect(addr)?;
+            sock.connect(addr).map_err(|e| rejected(&sock, e))?;
         }
 
         Ok(Stream { sock: sock })
     }
 
+    /// Opens a bonded SRT connection to all of the given endpoints.
+    #[cfg(feature = "bonding")]
+    pub fn connect_group(
+        &self,
+        group_type: GROUPTYPE,
+        endpoints: &[Endpoint],
+    ) -> io::Result<Group> {
+        sys::try_init()?;
+
+        let group = sys::Group::new(group_type)?;
+        self.configure(group.as_socket())?;
+
+        if self.nonblocking {
+            group.as_socket().set_send_nonblocking(true)?;
+            group.as_socket().set_recv_nonblocking(true)?;
+            match group.connect(endpoints) {
+                Ok(_) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(rejected(group.as_socket(), e)),
+            }
+        } else {
+            group.connect(endpoints).map_err(|e| rejected(group.as_socket(), e))?;
+        }
+
+        Ok(Group { group: group })
+    }
+
     /// Creates a new `Listener` which will be bound to the specified
     /// address.
     pub fn bind(&self, addr: &SocketAddr) -> io::Result<Listener> {
-        sys::init();
+        sys::try_init()?;
 
-        let sock = Socket::new(addr)?;
+        let mut sock = Socket::new()?;
+        sock.set_observer(self.observer.clone());
+        self.configure(&sock)?;
         sock.bind(addr)?;
-        sock.listen(128)?;
 
-        sock.set_trans_type(self.trans_type)?;
-        sock.set_payload_size(self.payload_size)?;
+        self.listen_socket(sock)
+    }
+
+    /// Creates a new `Listener` on top of an existing UDP socket.
+    ///
+    /// The UDP socket is owned by SRT from now on and closed together with
+    /// the returned `Listener`.
+    pub fn bind_from(&self, udp: UdpSocket) -> io::Result<Listener> {
+        sys::try_init()?;
+
+        let mut sock = Socket::new()?;
+        sock.set_observer(self.observer.clone());
+        self.configure(&sock)?;
+        sock.bind_acquire(udp)?;
+
+        self.listen_socket(sock)
+    }
+
+    fn listen_socket(&self, sock: Socket) -> io::Result<Listener> {
+        #[cfg(feature = "bonding")]
+        {
+            if self.group_connect {
+                sock.set_group_connect(true)?;
+            }
+        }
+        sock.listen(self.backlog)?;
         if self.nonblocking {
             sock.set_recv_nonblocking(true)?;
         }
@@ -115,6 +404,51 @@ impl Builder {
         Ok(Listener { sock: sock })
     }
 
+    /// Applies the options which have to be set before binding or
+    /// connecting.
+    fn configure(&self, sock: &Socket) -> io::Result<()> {
+        // the transmission type resets other options, it has to go first
+        sock.set_trans_type(self.trans_type)?;
+        if let Some(payload_size) = self.payload_size {
+            sock.set_payload_size(payload_size)?;
+        }
+        if let Some(message_api) = self.message_api {
+            sock.set_message_api(message_api)?;
+        }
+        if let Some(congestion) = self.congestion {
+            sock.set_congestion(congestion.as_str())?;
+        }
+        if let Some(reuse_addr) = self.reuse_addr {
+            sock.set_reuse_addr(reuse_addr)?;
+        }
+        if let Some(linger) = self.linger {
+            sock.set_linger(linger)?;
+        }
+        if let Some(ref fec) = self.fec {
+            fec.validate()?;
+            sock.set_packet_filter(&fec.to_string())?;
+        }
+        if let Some(ttl) = self.ip_ttl {
+            sock.set_ip_ttl(ttl)?;
+        }
+        if let Some(tos) = self.ip_tos {
+            sock.set_ip_tos(tos)?;
+        }
+        if let Some(version) = self.min_peer_version {
+            sock.set_min_version(version)?;
+        }
+        if let Some(latency) = self.latency {
+            sock.set_latency(latency)?;
+        }
+        if let Some(ref passphrase) = self.passphrase {
+            sock.set_passphrase(passphrase)?;
+        }
+        if let Some(timeout) = self.connect_timeout {
+            sock.set_connect_timeout(timeout)?;
+        }
+        Ok(())
+    }
+
     /// Accept a new incoming connection
     pub fn accept(&self, stream: Stream) -> io::Result<Stream> {
         if self.nonblocking {
@@ -136,6 +470,269 @@ pub struct Stream {
     sock: Socket,
 }
 
+impl Stream {
+    /// Waits until all sent data is acknowledged by the peer.
+    ///
+    /// Fails with `TimedOut` if data is still pending after `timeout`, or
+    /// with `BrokenPipe` if the connection is lost in the meantime.
+    pub fn drain(&self, timeout: Option<Duration>) -> io::Result<()> {
+        const INTERVAL: Duration = Duration::from_millis(10);
+
+        let start = Instant::now();
+        while self.sock.snd_data()? > 0 {
+            if self.sock.is_broken()? || self.sock.is_closed()? {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe,
+                                          "connection lost before drained"));
+            }
+            if let Some(timeout) = timeout {
+                if start.elapsed() >= timeout {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                              "sending buffer not drained"));
+                }
+            }
+            thread::sleep(INTERVAL);
+        }
+        Ok(())
+    }
+
+    /// Closes the connection, reporting errors instead of ignoring them as
+    /// `Drop` does.
+    ///
+    /// With `linger` set, a blocking stream waits for unsent data first.
+    pub fn close(self) -> io::Result<()> {
+        self.sock.close()
+    }
+
+    /// Waits up to `timeout` for all sent data to be acknowledged, then
+    /// closes the connection.
+    ///
+    /// The connection is closed in any case; `TimedOut` means some data
+    /// may not have been delivered.
+    pub fn close_timeout(self, timeout: Duration) -> io::Result<()> {
+        let drained = self.drain(Some(timeout));
+        self.close()?;
+        drained
+    }
+
+    /// Returns how much data is waiting in the sending buffer, i.e. not
+    /// yet acknowledged by the peer.
+    pub fn send_buffer(&self) -> io::Result<BufferLevel> {
+        let (packets, bytes) = self.sock.snd_buffer()?;
+        Ok(BufferLevel {
+            packets: packets,
+            bytes: Some(bytes),
+            capacity: self.buffer_packets(self.sock.snd_buf()?)?,
+        })
+    }
+
+    /// Returns how much data is waiting in the receiving buffer to be
+    /// read.
+    pub fn recv_buffer(&self) -> io::Result<BufferLevel> {
+        Ok(BufferLevel {
+            packets: self.sock.rcv_data()?,
+            bytes: None,
+            capacity: self.buffer_packets(self.sock.rcv_buf()?)?,
+        })
+    }
+
+    // libsrt sizes its buffers in packets of (MSS - IP/UDP headers)
+    fn buffer_packets(&self, bytes: usize) -> io::Result<usize> {
+        Ok(bytes / (self.sock.mss()? - 28))
+    }
+
+    /// Returns the maximum payload size of one UDP packet (0 if unlimited).
+    pub fn payload_size(&self) -> io::Result<usize> {
+        self.sock.payload_size()
+    }
+
+    /// Returns the maximum segment size (UDP packet including the IP and
+    /// UDP headers).
+    pub fn mss(&self) -> io::Result<usize> {
+        self.sock.mss()
+    }
+
+    /// Returns the receiver buffering delay agreed with the peer.
+    pub fn latency(&self) -> io::Result<Duration> {
+        self.sock.latency()
+    }
+
+    /// Returns the IP time to live (IPv6 hop limit) of outgoing packets.
+    pub fn ip_ttl(&self) -> io::Result<u8> {
+        self.sock.ip_ttl()
+    }
+
+    /// Returns whether the local address may be shared with other sockets.
+    pub fn reuse_addr(&self) -> io::Result<bool> {
+        self.sock.reuse_addr()
+    }
+
+    /// Returns the IP type of service byte (IPv6 traffic class) of
+    /// outgoing packets.
+    pub fn ip_tos(&self) -> io::Result<u8> {
+        self.sock.ip_tos()
+    }
+
+    /// Returns the DSCP class of outgoing packets, `None` if the codepoint
+    /// is not one of the standard classes.
+    pub fn dscp(&self) -> io::Result<Option<Dscp>> {
+        self.sock.ip_tos().map(Dscp::from_tos)
+    }
+
+    /// Returns the largest message that can be sent in one write, or
+    /// `None` in byte stream mode where writes have no boundaries.
+    ///
+    /// In live mode a message has to fit into one packet: the payload
+    /// size, bounded by what the MSS leaves after the headers and the
+    /// AES-GCM authentication tag. In file mode a message has to fit into
+    /// the sending buffer.
+    pub fn max_message_size(&self) -> io::Result<Option<usize>> {
+        self.message_size(Socket::snd_buf)
+    }
+
+    /// Largest message that fits into the buffer `buf_size` returns the
+    /// size of, see `max_message_size`.
+    fn message_size(
+        &self,
+        buf_size: fn(&Socket) -> io::Result<usize>,
+    ) -> io::Result<Option<usize>> {
+        if !self.sock.message_api()? {
+            return Ok(None);
+        }
+
+        // IP + UDP headers, then the SRT data packet header
+        const UDP_HDR_V4: usize = 20 + 8;
+        const UDP_HDR_V6: usize = 40 + 8;
+        const SRT_DATA_HDR: usize = 16;
+
+        let mss = self.sock.mss()?;
+        let udp_hdr = match self.local_addr()? {
+            SocketAddr::V4(..) => UDP_HDR_V4,
+            SocketAddr::V6(..) => UDP_HDR_V6,
+        };
+        let mut packet_size = mss.saturating_sub(udp_hdr + SRT_DATA_HDR);
+        if self.sock.km_state()? == KM_STATE::SRT_KM_S_SECURED {
+            // libsrt < 1.5.2 only has AES-CTR, which adds no overhead
+            if let Ok(sys::CRYPTOMODE_AES_GCM) = self.sock.crypto_mode() {
+                packet_size = packet_size.saturating_sub(sys::AUTHTAG_MAX as usize);
+            }
+        }
+
+        if self.congestion()? == Congestion::Live {
+            let payload_size = self.sock.payload_size()?;
+            if payload_size > 0 {
+                return Ok(Some(cmp::min(payload_size, packet_size)));
+            }
+            return Ok(Some(packet_size));
+        }
+
+        // libsrt sizes its buffers in packets of (MSS - UDP headers)
+        let packets = buf_size(&self.sock)? / (mss - UDP_HDR_V4);
+        Ok(Some(packets * packet_size))
+    }
+
+    /// Splits `buf` into chunks of at most `max_message_size` bytes, each
+    /// of which can be sent in one write.
+    pub fn split_messages<'a>(&self, buf: &'a [u8]) -> io::Result<Chunks<'a, u8>> {
+        let size = match self.max_message_size()? {
+            Some(size) if size > 0 => size,
+            _ => cmp::max(buf.len(), 1),
+        };
+        Ok(buf.chunks(size))
+    }
+
+    /// Returns the SRT version the peer reported in the handshake.
+    pub fn peer_version(&self) -> io::Result<Version> {
+        self.sock.peer_version()
+    }
+
+    /// Polls for read readiness of a nonblocking stream, registering the
+    /// task of `cx` with the process-wide stream reactor (see
+    /// `stream::Handle::global`).
+    ///
+    /// `Ready` means a read may make progress (or report an error); once a
+    /// read fails with `WouldBlock`, poll again to wait for more data.
+    #[cfg(feature = "stream")]
+    pub fn poll_read_ready(&self, cx: &mut Context<'_>) -> task::Poll<io::Result<()>> {
+        match crate::stream::Handle::global() {
+            Ok(handle) => handle.poll_ready(&self.sock, EventKind::readable(), cx),
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
+    }
+
+    /// Polls for write readiness of a nonblocking stream, see
+    /// `poll_read_ready`.
+    #[cfg(feature = "stream")]
+    pub fn poll_write_ready(&self, cx: &mut Context<'_>) -> task::Poll<io::Result<()>> {
+        match crate::stream::Handle::global() {
+            Ok(handle) => handle.poll_ready(&self.sock, EventKind::writable(), cx),
+            Err(e) => task::Poll::Ready(Err(e)),
+        }
+    }
+
+    /// Returns why the connection was rejected, e.g. after a nonblocking
+    /// connect failed (see `Poll`).
+    pub fn reject_reason(&self) -> RejectReason {
+        RejectReason::from_raw(self.sock.reject_reason())
+    }
+
+    /// Returns the congestion controller in use.
+    pub fn congestion(&self) -> io::Result<Congestion> {
+        self.sock.congestion()?.parse()
+    }
+
+    /// Returns the packet filter configuration negotiated with the peer.
+    pub fn fec(&self) -> io::Result<Option<Fec>> {
+        let filter = self.sock.packet_filter()?;
+        if filter.is_empty() {
+            Ok(None)
+        } else {
+            parse_fec(&filter, true).map(Some)
+        }
+    }
+
+    /// Converts the stream into one that keeps message boundaries, failing
+    /// if it was set up as a byte stream.
+    pub fn into_message_stream(self) -> io::Result<MessageStream> {
+        if !self.sock.message_api()? {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "not in message mode"));
+        }
+        let max_message_size = if self.congestion()? == Congestion::Live {
+            self.max_message_size()?
+        } else {
+            None
+        };
+        // a message larger than the receiving buffer can't be delivered
+        let recv_buffer_size = self.message_size(Socket::rcv_buf)?.unwrap_or(0);
+        Ok(MessageStream {
+            sock: self.sock,
+            max_message_size: max_message_size,
+            recv_buffer_size: recv_buffer_size,
+        })
+    }
+
+    /// Converts the stream into a byte stream, failing if it was set up
+    /// with message boundaries.
+    pub fn into_byte_stream(self) -> io::Result<ByteStream> {
+        if self.sock.message_api()? {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "not in byte stream mode"));
+        }
+        Ok(ByteStream { sock: self.sock })
+    }
+
+    /// Converts a connection accepted from a bonded caller into a `Group`.
+    ///
+    /// Returns the stream back if it is a single connection.
+    #[cfg(feature = "bonding")]
+    pub fn into_group(self) -> Result<Group, Stream> {
+        match sys::Group::from_socket(self.sock) {
+            Ok(group) => Ok(Group { group: group }),
+            Err(sock) => Err(Stream { sock: sock }),
+        }
+    }
+}
+
 impl AsSocket for Stream {
     fn as_socket(&self) -> &Socket {
         &self.sock
@@ -216,76 +813,1339 @@ impl fmt::Debug for Stream {
     }
 }
 
+/// Occupancy of a sending or receiving buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BufferLevel {
+    packets: usize,
+    bytes: Option<usize>,
+    capacity: usize,
+}
+
+impl BufferLevel {
+    /// Number of packets in the buffer
+    pub fn packets(&self) -> usize {
+        self.packets
+    }
+
+    /// Number of bytes in the buffer (sending buffer only)
+    pub fn bytes(&self) -> Option<usize> {
+        self.bytes
+    }
+
+    /// Buffer size in packets
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Fraction of the buffer in use, from 0.0 to 1.0
+    pub fn fill(&self) -> f64 {
+        if self.capacity == 0 {
+            0.0
+        } else {
+            self.packets as f64 / self.capacity as f64
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
-// SRT listeners
+// SRT message and byte streams
 ////////////////////////////////////////////////////////////////////////////////
 
-/// A SRT input socket server, listening for connections.
-pub struct Listener {
-    sock: Socket
+/// A SRT stream in message mode: every read returns exactly one message
+/// and every write sends exactly one message.
+pub struct MessageStream {
+    sock: Socket,
+    max_message_size: Option<usize>,
+    /// Largest message the receiving buffer holds
+    recv_buffer_size: usize,
 }
 
-impl Listener {
-    /// Accept a new incoming connection from this listener.
-    pub fn accept(&self) -> io::Result<(Stream, SocketAddr)> {
-        let (sock, addr) = self.as_socket().accept()?;
-        Ok((Stream { sock: sock }, addr))
+impl MessageStream {
+    /// Receives one message.
+    ///
+    /// SRT silently discards the part of a message that doesn't fit into
+    /// the buffer, so a buffer smaller than `recv_message_size` is
+    /// rejected.
+    pub fn recv_msg(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let max_message_size = self.recv_message_size();
+        if buf.len() < max_message_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("receive buffer ({} bytes) is smaller than the \
+                         largest message ({} bytes)",
+                        buf.len(), max_message_size)));
+        }
+        self.sock.recv(buf)
+    }
+
+    /// Sends `buf` as one message.
+    pub fn send_msg(&self, buf: &[u8]) -> io::Result<()> {
+        if let Some(max_message_size) = self.max_message_size {
+            if buf.len() > max_message_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("message ({} bytes) is larger than the \
+                             largest message ({} bytes)",
+                            buf.len(), max_message_size)));
+            }
+        }
+        let nsent = self.sock.send(buf)?;
+        if nsent != buf.len() {
+            return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                      "message partially sent"));
+        }
+        Ok(())
+    }
+
+    /// Largest message this stream carries, if known.
+    ///
+    /// In live mode it is the payload size. In file mode messages may be
+    /// as large as the sender's buffer, so it is unknown unless agreed
+    /// with the peer and set with `set_max_message_size`.
+    pub fn max_message_size(&self) -> Option<usize> {
+        self.max_message_size
+    }
+
+    /// Smallest buffer `recv_msg` accepts: `max_message_size` if known,
+    /// otherwise the largest message the receiving buffer holds (some
+    /// megabytes by default).
+    pub fn recv_message_size(&self) -> usize {
+        self.max_message_size.unwrap_or(self.recv_buffer_size)
+    }
+
+    pub fn set_max_message_size(&mut self, max_message_size: Option<usize>) {
+        self.max_message_size = max_message_size;
+    }
+
+    /// Converts back into a plain `Stream`.
+    pub fn into_stream(self) -> Stream {
+        Stream { sock: self.sock }
     }
 }
 
-impl AsSocket for Listener {
+impl AsSocket for MessageStream {
     fn as_socket(&self) -> &Socket {
         &self.sock
     }
 }
 
-impl AsSocket for &Listener {
+impl AsSocket for &MessageStream {
     fn as_socket(&self) -> &Socket {
         &self.sock
     }
 }
 
-impl Bind for Listener {}
+impl Bind for MessageStream {}
 
-impl fmt::Debug for Listener {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut res = f.debug_struct("Listener");
+impl Connect for MessageStream {}
 
-        if let Ok(addr) = self.local_addr() {
-            res.field("listen", &addr);
-        }
+impl Read for MessageStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.recv_msg(buf)
+    }
+}
 
-        res.finish()
+impl Read for &MessageStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.recv_msg(buf)
     }
 }
 
-////////////////////////////////////////////////////////////////////////////////
-// SRT Poll
-////////////////////////////////////////////////////////////////////////////////
+impl Write for MessageStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send_msg(buf).map(|_| buf.len())
+    }
 
-/// Polls for readiness events on all registered values.
-pub struct Poll {
-    poll: sys::Poll,
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
-impl Poll {
-    /// Return a new `Poll` handle.
-    pub fn new() -> io::Result<Poll> {
-        Ok(Poll {
-            poll: sys::Poll::new()?,
-        })
+impl Write for &MessageStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send_msg(buf).map(|_| buf.len())
     }
 
-    /// Register an `AsSocket` instance with the `Poll` instance.
-    pub fn register<S: AsSocket>(
-        &self,
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Debug for MessageStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut res = f.debug_struct("MessageStream");
+
+        if let Ok(addr) = self.local_addr() {
+            res.field("local", &addr);
+        }
+
+        if let Ok(peer) = self.peer_addr() {
+            res.field("peer", &peer);
+        }
+
+        res.field("max_message_size", &self.max_message_size);
+
+        res.finish()
+    }
+}
+
+/// A SRT stream in file mode without message boundaries, behaving like
+/// a TCP stream: reads and writes may transfer any part of the data.
+pub struct ByteStream {
+    sock: Socket,
+}
+
+impl ByteStream {
+    /// Converts back into a plain `Stream`.
+    pub fn into_stream(self) -> Stream {
+        Stream { sock: self.sock }
+    }
+}
+
+impl AsSocket for ByteStream {
+    fn as_socket(&self) -> &Socket {
+        &self.sock
+    }
+}
+
+impl AsSocket for &ByteStream {
+    fn as_socket(&self) -> &Socket {
+        &self.sock
+    }
+}
+
+impl Bind for ByteStream {}
+
+impl Connect for ByteStream {}
+
+impl Read for ByteStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.sock.recv(buf)
+    }
+}
+
+impl Read for &ByteStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.sock.recv(buf)
+    }
+}
+
+impl Write for ByteStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sock.send(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Write for &ByteStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sock.send(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ByteStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut res = f.debug_struct("ByteStream");
+
+        if let Ok(addr) = self.local_addr() {
+            res.field("local", &addr);
+        }
+
+        if let Ok(peer) = self.peer_addr() {
+            res.field("peer", &peer);
+        }
+
+        res.finish()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// SRT groups
+////////////////////////////////////////////////////////////////////////////////
+
+/// A group of SRT connections (broadcast or main/backup redundancy) that
+/// reads and writes like a single stream.
+#[cfg(feature = "bonding")]
+pub struct Group {
+    group: sys::Group,
+}
+
+#[cfg(feature = "bonding")]
+impl Group {
+    /// Returns the current status of all group members.
+    pub fn members(&self) -> io::Result<Vec<Member>> {
+        self.group.members()
+    }
+}
+
+#[cfg(feature = "bonding")]
+impl AsSocket for Group {
+    fn as_socket(&self) -> &Socket {
+        self.group.as_socket()
+    }
+}
+
+#[cfg(feature = "bonding")]
+impl AsSocket for &Group {
+    fn as_socket(&self) -> &Socket {
+        self.group.as_socket()
+    }
+}
+
+#[cfg(feature = "bonding")]
+impl Read for Group {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.group.recv(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.group.recv_vectored(bufs)
+    }
+}
+
+#[cfg(feature = "bonding")]
+impl Read for &Group {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.group.recv(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.group.recv_vectored(bufs)
+    }
+}
+
+#[cfg(feature = "bonding")]
+impl Write for Group {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.group.send(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.group.send_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bonding")]
+impl Write for &Group {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.group.send(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.group.send_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bonding")]
+impl fmt::Debug for Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut res = f.debug_struct("Group");
+
+        if let Ok(members) = self.members() {
+            let peers = members.iter().filter_map(|m| m.peer_addr()).collect::<Vec<_>>();
+            res.field("peers", &peers);
+        }
+
+        res.finish()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// SRT congestion control
+////////////////////////////////////////////////////////////////////////////////
+
+/// Congestion controller (`SRTO_CONGESTION`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Congestion {
+    /// Paced sending at the configured bandwidth, for live streaming
+    Live,
+    /// Window based, as fast as the link allows, for bulk transfers
+    File,
+}
+
+impl Congestion {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Congestion::Live => "live",
+            Congestion::File => "file",
+        }
+    }
+}
+
+impl fmt::Display for Congestion {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Congestion {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Congestion> {
+        match s {
+            "live" => Ok(Congestion::Live),
+            "file" => Ok(Congestion::File),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                    format!("unknown congestion controller {:?}", s))),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// SRT connection rejection
+////////////////////////////////////////////////////////////////////////////////
+
+/// Why a connection was rejected during the handshake.
+///
+/// A refused connect fails with an `io::Error` of kind `ConnectionRefused`
+/// carrying the reason, see `RejectReason::from_error`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RejectReason {
+    Unknown,
+    /// A system function failed
+    System,
+    /// Rejected by the peer
+    Peer,
+    /// Internal resource allocation failed
+    Resource,
+    /// Incorrect data in the handshake
+    Rogue,
+    /// The listener's backlog is full
+    Backlog,
+    /// Internal program error
+    Internal,
+    /// The socket is closing
+    Close,
+    /// The peer is older than the minimum version (see
+    /// `Builder::min_peer_version`)
+    Version,
+    /// Rendezvous cookie collision
+    RendezvousCookie,
+    /// Wrong passphrase
+    BadSecret,
+    /// Passphrase required or unexpected
+    Unsecure,
+    /// Message API settings differ
+    MessageApi,
+    /// Incompatible congestion controllers
+    Congestion,
+    /// Incompatible packet filters
+    Filter,
+    /// Incompatible group settings
+    Group,
+    /// The handshake timed out
+    Timeout,
+    /// Incompatible encryption settings, e.g. the cipher mode (libsrt
+    /// 1.5.2 and later)
+    Crypto,
+    /// A code unknown to the bindings, e.g. of 1000 and above, set by the
+    /// peer application
+    Other(int),
+}
+
+impl RejectReason {
+    pub fn from_raw(reason: int) -> RejectReason {
+        match reason {
+            0 => RejectReason::Unknown,
+            1 => RejectReason::System,
+            2 => RejectReason::Peer,
+            3 => RejectReason::Resource,
+            4 => RejectReason::Rogue,
+            5 => RejectReason::Backlog,
+            6 => RejectReason::Internal,
+            7 => RejectReason::Close,
+            8 => RejectReason::Version,
+            9 => RejectReason::RendezvousCookie,
+            10 => RejectReason::BadSecret,
+            11 => RejectReason::Unsecure,
+            12 => RejectReason::MessageApi,
+            13 => RejectReason::Congestion,
+            14 => RejectReason::Filter,
+            15 => RejectReason::Group,
+            16 => RejectReason::Timeout,
+            17 => RejectReason::Crypto,
+            reason => RejectReason::Other(reason),
+        }
+    }
+
+    pub fn as_raw(&self) -> int {
+        match *self {
+            RejectReason::Unknown => 0,
+            RejectReason::System => 1,
+            RejectReason::Peer => 2,
+            RejectReason::Resource => 3,
+            RejectReason::Rogue => 4,
+            RejectReason::Backlog => 5,
+            RejectReason::Internal => 6,
+            RejectReason::Close => 7,
+            RejectReason::Version => 8,
+            RejectReason::RendezvousCookie => 9,
+            RejectReason::BadSecret => 10,
+            RejectReason::Unsecure => 11,
+            RejectReason::MessageApi => 12,
+            RejectReason::Congestion => 13,
+            RejectReason::Filter => 14,
+            RejectReason::Group => 15,
+            RejectReason::Timeout => 16,
+            RejectReason::Crypto => 17,
+            RejectReason::Other(reason) => reason,
+        }
+    }
+
+    /// Returns the reason a connect failed with, if it was rejected.
+    pub fn from_error(err: &io::Error) -> Option<RejectReason> {
+        err.get_ref()
+            .and_then(|e| e.downcast_ref::<RejectReason>())
+            .cloned()
+    }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "connection rejected: {}", sys::rejectreason_str(self.as_raw()))
+    }
+}
+
+impl std::error::Error for RejectReason {}
+
+fn rejected(sock: &Socket, err: io::Error) -> io::Error {
+    if err.kind() == io::ErrorKind::ConnectionRefused {
+        let reason = RejectReason::from_raw(sock.reject_reason());
+        io::Error::new(io::ErrorKind::ConnectionRefused, reason)
+    } else {
+        err
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// SRT IP marking
+////////////////////////////////////////////////////////////////////////////////
+
+/// Standard DSCP classes (RFC 2474, 2597, 3246) for marking outgoing
+/// packets, e.g. `AF41` for interactive video.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Dscp {
+    /// Best effort (the default)
+    CS0,
+    CS1,
+    CS2,
+    CS3,
+    CS4,
+    CS5,
+    CS6,
+    CS7,
+    AF11,
+    AF12,
+    AF13,
+    AF21,
+    AF22,
+    AF23,
+    AF31,
+    AF32,
+    AF33,
+    AF41,
+    AF42,
+    AF43,
+    /// Expedited forwarding
+    EF,
+}
+
+impl Dscp {
+    const ALL: [Dscp; 21] = [
+        Dscp::CS0, Dscp::CS1, Dscp::CS2, Dscp::CS3,
+        Dscp::CS4, Dscp::CS5, Dscp::CS6, Dscp::CS7,
+        Dscp::AF11, Dscp::AF12, Dscp::AF13,
+        Dscp::AF21, Dscp::AF22, Dscp::AF23,
+        Dscp::AF31, Dscp::AF32, Dscp::AF33,
+        Dscp::AF41, Dscp::AF42, Dscp::AF43,
+        Dscp::EF,
+    ];
+
+    /// Returns the 6 bit codepoint.
+    pub fn codepoint(&self) -> u8 {
+        match *self {
+            Dscp::CS0 => 0,
+            Dscp::CS1 => 8,
+            Dscp::CS2 => 16,
+            Dscp::CS3 => 24,
+            Dscp::CS4 => 32,
+            Dscp::CS5 => 40,
+            Dscp::CS6 => 48,
+            Dscp::CS7 => 56,
+            Dscp::AF11 => 10,
+            Dscp::AF12 => 12,
+            Dscp::AF13 => 14,
+            Dscp::AF21 => 18,
+            Dscp::AF22 => 20,
+            Dscp::AF23 => 22,
+            Dscp::AF31 => 26,
+            Dscp::AF32 => 28,
+            Dscp::AF33 => 30,
+            Dscp::AF41 => 34,
+            Dscp::AF42 => 36,
+            Dscp::AF43 => 38,
+            Dscp::EF => 46,
+        }
+    }
+
+    pub fn from_codepoint(codepoint: u8) -> Option<Dscp> {
+        Dscp::ALL.iter().cloned().find(|dscp| dscp.codepoint() == codepoint)
+    }
+
+    /// Returns the class a type of service byte is marked with.
+    pub fn from_tos(tos: u8) -> Option<Dscp> {
+        Dscp::from_codepoint(tos >> 2)
+    }
+
+    /// Marks a type of service byte with this class, keeping its ECN bits.
+    pub fn to_tos(&self, tos: u8) -> u8 {
+        self.codepoint() << 2 | tos & 0x03
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// SRT packet filter
+////////////////////////////////////////////////////////////////////////////////
+
+/// Arrangement of the FEC column groups.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FecLayout {
+    /// Columns start at the same packet as the rows
+    Even,
+    /// Columns are shifted by one packet each, spreading the FEC packets
+    Staircase,
+}
+
+/// Cooperation of FEC with retransmission (ARQ).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FecArq {
+    /// Retransmission requests are sent as usual, next to FEC recovery
+    Always,
+    /// Retransmission is requested only for packets FEC failed to recover
+    OnReq,
+    /// Retransmission is disabled, FEC is the only recovery
+    Never,
+}
+
+/// Configuration of the built-in `fec` packet filter, e.g.
+/// `fec,cols:10,rows:5,layout:staircase,arq:onreq`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Fec {
+    pub cols: u32,
+    pub rows: i32,
+    pub layout: Option<FecLayout>,
+    pub arq: Option<FecArq>,
+}
+
+impl Fec {
+    /// FEC with the given number of columns (row-only if `rows` is not set)
+    pub fn new(cols: u32) -> Fec {
+        Fec {
+            cols: cols,
+            rows: 1,
+            layout: None,
+            arq: None,
+        }
+    }
+
+    /// Number of rows (negative to disable the row FEC packets)
+    pub fn rows(mut self, rows: i32) -> Self {
+        self.rows = rows;
+        self
+    }
+
+    pub fn layout(mut self, layout: FecLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    pub fn arq(mut self, arq: FecArq) -> Self {
+        self.arq = Some(arq);
+        self
+    }
+
+    /// Checks the configuration before it is handed to SRT.
+    pub fn validate(&self) -> io::Result<()> {
+        if self.cols == 0 {
+            return Err(invalid_fec("cols must be at least 1"));
+        }
+        if self.rows == 0 {
+            return Err(invalid_fec("rows must not be 0"));
+        }
+        if self.cols == 1 && self.rows.abs() == 1 {
+            return Err(invalid_fec("a 1x1 matrix protects nothing"));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Fec {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "fec,cols:{},rows:{}", self.cols, self.rows)?;
+        match self.layout {
+            Some(FecLayout::Even) => write!(fmt, ",layout:even")?,
+            Some(FecLayout::Staircase) => write!(fmt, ",layout:staircase")?,
+            None => {}
+        }
+        match self.arq {
+            Some(FecArq::Always) => write!(fmt, ",arq:always")?,
+            Some(FecArq::OnReq) => write!(fmt, ",arq:onreq")?,
+            Some(FecArq::Never) => write!(fmt, ",arq:never")?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Fec {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Fec> {
+        parse_fec(s, false)
+    }
+}
+
+/// Parses a filter configuration. A `lenient` parse skips the keys unknown
+/// to the bindings, which a newer libsrt may negotiate; a user's
+/// configuration is held to the keys known here so that typos are caught.
+fn parse_fec(s: &str, lenient: bool) -> io::Result<Fec> {
+    let mut params = s.trim().split(',');
+    if params.next() != Some("fec") {
+        return Err(invalid_fec("not a fec filter"));
+    }
+
+    let mut cols = None;
+    let mut fec = Fec::new(0);
+    for param in params {
+        let mut kv = param.splitn(2, ':');
+        let key = kv.next().unwrap_or("");
+        let value = kv.next().ok_or_else(|| invalid_fec(param))?;
+        match key {
+            "cols" => {
+                cols = Some(value.parse().map_err(|_| invalid_fec(param))?);
+            }
+            "rows" => {
+                fec.rows = value.parse().map_err(|_| invalid_fec(param))?;
+            }
+            "layout" => {
+                fec.layout = Some(match value {
+                    "even" => FecLayout::Even,
+                    "staircase" => FecLayout::Staircase,
+                    _ => return Err(invalid_fec(param)),
+                });
+            }
+            "arq" => {
+                fec.arq = Some(match value {
+                    "always" => FecArq::Always,
+                    "onreq" => FecArq::OnReq,
+                    "never" => FecArq::Never,
+                    _ => return Err(invalid_fec(param)),
+                });
+            }
+            // added by a newer libsrt, has no counterpart here
+            _ if lenient => {}
+            _ => return Err(invalid_fec(param)),
+        }
+    }
+
+    fec.cols = cols.ok_or_else(|| invalid_fec("cols is required"))?;
+    fec.validate()?;
+    Ok(fec)
+}
+
+fn invalid_fec(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput,
+                   format!("invalid fec configuration: {}", msg))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// SRT listeners
+////////////////////////////////////////////////////////////////////////////////
+
+/// A SRT input socket server, listening for connections.
+pub struct Listener {
+    sock: Socket
+}
+
+impl Listener {
+    /// Accept a new incoming connection from this listener.
+    pub fn accept(&self) -> io::Result<(Stream, SocketAddr)> {
+        let (sock, addr) = self.as_socket().accept()?;
+        Ok((Stream { sock: sock }, addr))
+    }
+
+    /// Stops listening, reporting errors instead of ignoring them as `Drop`
+    /// does.
+    pub fn close(self) -> io::Result<()> {
+        self.sock.close()
+    }
+}
+
+impl AsSocket for Listener {
+    fn as_socket(&self) -> &Socket {
+        &self.sock
+    }
+}
+
+impl AsSocket for &Listener {
+    fn as_socket(&self) -> &Socket {
+        &self.sock
+    }
+}
+
+impl Bind for Listener {}
+
+impl fmt::Debug for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut res = f.debug_struct("Listener");
+
+        if let Ok(addr) = self.local_addr() {
+            res.field("listen", &addr);
+        }
+
+        res.finish()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// SRT servers
+////////////////////////////////////////////////////////////////////////////////
+
+/// Accepts connections on a listener and runs a handler for each of them
+/// on a pool of threads.
+///
+/// Handlers get blocking streams. Connections beyond `max_connections`
+/// are left in the listener's backlog until a handler returns.
+pub struct Server {
+    threads: usize,
+    max_connections: Option<usize>,
+    on_error: Arc<dyn Fn(SocketAddr, io::Error) + Send + Sync>,
+    handle: ServerHandle,
+}
+
+/// Stops a running `Server` from another thread.
+#[derive(Clone)]
+pub struct ServerHandle {
+    inner: Arc<ServerState>,
+}
+
+struct ServerState {
+    shutdown: AtomicBool,
+    active: Mutex<usize>,
+    idle: Condvar,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server {
+            threads: 4,
+            max_connections: None,
+            on_error: Arc::new(|addr, err| warn!("connection from {} failed: {}", addr, err)),
+            handle: ServerHandle {
+                inner: Arc::new(ServerState {
+                    shutdown: AtomicBool::new(false),
+                    active: Mutex::new(0),
+                    idle: Condvar::new(),
+                }),
+            },
+        }
+    }
+
+    /// Number of handler threads (4 by default)
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = cmp::max(threads, 1);
+        self
+    }
+
+    /// Maximum number of connections handled or waiting for a handler
+    /// thread (the number of threads by default)
+    ///
+    /// Connections beyond the number of threads are accepted but wait,
+    /// possibly for as long as a handler runs, for a thread to be free.
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(cmp::max(max_connections, 1));
+        self
+    }
+
+    /// Called with the peer address whenever a handler fails or panics
+    /// (errors are logged by default)
+    pub fn on_error<F>(mut self, on_error: F) -> Self
+    where
+        F: Fn(SocketAddr, io::Error) + Send + Sync + 'static,
+    {
+        self.on_error = Arc::new(on_error);
+        self
+    }
+
+    pub fn handle(&self) -> ServerHandle {
+        self.handle.clone()
+    }
+
+    /// Serves connections until shut down through a `ServerHandle`, then
+    /// waits for the running handlers to return.
+    ///
+    /// Fails if polling the listener fails or it stops listening. A
+    /// connection that cannot be accepted or set up is reported to
+    /// `on_error` (with an unspecified address if the peer is unknown).
+    pub fn serve<H>(self, listener: Listener, handler: H) -> io::Result<()>
+    where
+        H: Fn(Stream, SocketAddr) -> io::Result<()> + Send + Sync + 'static,
+    {
+        const TIMEOUT: Duration = Duration::from_millis(100);
+
+        let handler = Arc::new(handler);
+        let (tx, rx) = mpsc::channel::<(Stream, SocketAddr)>();
+        let rx = Arc::new(Mutex::new(rx));
+        let workers = (0..self.threads)
+            .map(|i| {
+                let (rx, handler) = (rx.clone(), handler.clone());
+                let (on_error, state) = (self.on_error.clone(), self.handle.inner.clone());
+                thread::Builder::new()
+                    .name(format!("srt-server-{}", i))
+                    .spawn(move || loop {
+                        let job = rx.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                        let (stream, addr) = match job {
+                            Ok(job) => job,
+                            Err(_closed) => break,
+                        };
+                        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                            handler(stream, addr)
+                        }));
+                        match res {
+                            Ok(Ok(())) => {}
+                            Ok(Err(e)) => on_error(addr, e),
+                            Err(_panic) => {
+                                on_error(addr, io::Error::other("connection handler panicked"));
+                            }
+                        }
+                        *state.active.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+                        state.idle.notify_one();
+                    })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let res = self.accept_loop(&listener, &tx, TIMEOUT);
+
+        // stop accepting and close the connections no handler took yet
+        drop(listener);
+        drop(tx);
+        let state = &self.handle.inner;
+        while let Ok((stream, addr)) = rx.lock().unwrap_or_else(|e| e.into_inner()).try_recv() {
+            trace!("closing connection from {}", addr);
+            drop(stream);
+            *state.active.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+        }
+        for worker in workers {
+            drop(worker.join());
+        }
+        res
+    }
+
+    fn accept_loop(
+        &self,
+        listener: &Listener,
+        tx: &mpsc::Sender<(Stream, SocketAddr)>,
+        timeout: Duration,
+    ) -> io::Result<()> {
+        let state = &self.handle.inner;
+        let max_connections = self.max_connections.unwrap_or(self.threads);
+        let poll = sys::Poll::new()?;
+        poll.register(listener.as_socket(), Token(0), EventKind::readable())?;
+        listener.as_socket().set_recv_nonblocking(true)?;
+
+        let mut events = Events::with_capacity(1);
+        while !state.shutdown.load(Ordering::SeqCst) {
+            {
+                let active = state.active.lock().unwrap_or_else(|e| e.into_inner());
+                if *active >= max_connections {
+                    // leave further connections in the backlog
+                    drop(state.idle.wait_timeout(active, timeout));
+                    continue;
+                }
+            }
+
+            events.clear();
+            poll.poll(&mut events, Some(timeout))?;
+            if events.is_empty() {
+                continue;
+            }
+
+            loop {
+                let (stream, addr) = match listener.accept() {
+                    Ok(conn) => conn,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) => match listener.state() {
+                        // only this connection failed
+                        SocketState::Listening => {
+                            (self.on_error)(SocketAddr::from(([0, 0, 0, 0], 0)), e);
+                            break;
+                        }
+                        _ => return Err(e),
+                    },
+                };
+                trace!("connection established from {}", addr);
+                // accepted sockets inherit the nonblocking listener mode
+                let blocking = stream.sock.set_recv_nonblocking(false)
+                    .and_then(|()| stream.sock.set_send_nonblocking(false));
+                if let Err(e) = blocking {
+                    (self.on_error)(addr, e);
+                    continue;
+                }
+
+                let mut active = state.active.lock().unwrap_or_else(|e| e.into_inner());
+                *active += 1;
+                let full = *active >= max_connections;
+                drop(active);
+                if tx.send((stream, addr)).is_err() {
+                    return Err(io::Error::other("server threads are gone"));
+                }
+                if full {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Server {
+    fn default() -> Server {
+        Server::new()
+    }
+}
+
+impl ServerHandle {
+    /// Stops accepting connections, closing those not taken by a handler
+    /// thread yet; `Server::serve` returns once the running handlers are
+    /// done.
+    pub fn shutdown(&self) {
+        self.inner.shutdown.store(true, Ordering::SeqCst);
+        self.inner.idle.notify_all();
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.inner.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of connections handled or waiting for a
+    /// handler thread.
+    pub fn active(&self) -> usize {
+        *self.inner.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// SRT socket state
+////////////////////////////////////////////////////////////////////////////////
+
+/// State of a SRT socket.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SocketState {
+    /// Created, not yet bound
+    Init,
+    /// Bound to a local address
+    Opened,
+    /// Listening for incoming connections
+    Listening,
+    /// Handshake in progress
+    Connecting,
+    Connected,
+    /// Connection lost, the socket is unusable
+    Broken,
+    Closing,
+    Closed,
+    /// The socket id is unknown (closed and released)
+    NonExist,
+}
+
+impl SocketState {
+    /// Returns true if the socket can't get back to a usable state.
+    pub fn is_terminal(&self) -> bool {
+        match *self {
+            SocketState::Broken
+            | SocketState::Closing
+            | SocketState::Closed
+            | SocketState::NonExist => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<SOCKSTATUS> for SocketState {
+    fn from(status: SOCKSTATUS) -> SocketState {
+        match status {
+            SOCKSTATUS::SRTS_INIT => SocketState::Init,
+            SOCKSTATUS::SRTS_OPENED => SocketState::Opened,
+            SOCKSTATUS::SRTS_LISTENING => SocketState::Listening,
+            SOCKSTATUS::SRTS_CONNECTING => SocketState::Connecting,
+            SOCKSTATUS::SRTS_CONNECTED => SocketState::Connected,
+            SOCKSTATUS::SRTS_BROKEN => SocketState::Broken,
+            SOCKSTATUS::SRTS_CLOSING => SocketState::Closing,
+            SOCKSTATUS::SRTS_CLOSED => SocketState::Closed,
+            SOCKSTATUS::SRTS_NONEXIST => SocketState::NonExist,
+        }
+    }
+}
+
+/// A state transition reported by [`StateWatcher::poll`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StateChange {
+    token: Token,
+    from: SocketState,
+    to: SocketState,
+}
+
+impl StateChange {
+    pub fn token(&self) -> Token {
+        self.token
+    }
+
+    pub fn from(&self) -> SocketState {
+        self.from
+    }
+
+    pub fn to(&self) -> SocketState {
+        self.to
+    }
+}
+
+/// Reports state transitions of a set of sockets, e.g. for a supervisor
+/// that reacts to CONNECTING -> BROKEN.
+///
+/// Sockets are registered for error events only, which wake the watcher
+/// on failures at once; other transitions (e.g. CONNECTING -> CONNECTED)
+/// are noticed by the next `poll`, at the latest when its timeout expires.
+/// A socket is unwatched automatically after reaching a terminal state.
+pub struct StateWatcher {
+    poll: sys::Poll,
+    watched: HashMap<Token, (SRTSOCKET, SocketState)>,
+}
+
+impl StateWatcher {
+    pub fn new() -> io::Result<StateWatcher> {
+        Ok(StateWatcher {
+            poll: sys::Poll::new()?,
+            watched: HashMap::new(),
+        })
+    }
+
+    /// Starts watching a socket, returning its current state.
+    pub fn watch<S: AsSocket>(&mut self, socket: &S, token: Token) -> io::Result<SocketState> {
+        let sock = socket.as_socket();
+        let state = socket.state();
+        self.poll.register(sock, token, EventKind::error())?;
+        self.watched.insert(token, (sock.as_raw(), state));
+        Ok(state)
+    }
+
+    /// Stops watching a socket.
+    pub fn unwatch(&mut self, token: Token) -> io::Result<()> {
+        match self.watched.remove(&token) {
+            Some((sock, _)) => self.poll.deregister_raw(sock),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the number of watched sockets.
+    pub fn len(&self) -> usize {
+        self.watched.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watched.is_empty()
+    }
+
+    /// Waits for an error event or until `timeout` expires, then returns
+    /// the transitions since the previous call.
+    pub fn poll(&mut self, timeout: Option<Duration>) -> io::Result<Vec<StateChange>> {
+        if self.watched.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut events = Events::with_capacity(self.watched.len());
+        self.poll.poll(&mut events, timeout)?;
+
+        let mut changes = Vec::new();
+        for (token, &mut (sock, ref mut state)) in self.watched.iter_mut() {
+            let current = SocketState::from(sys::sockstate(sock));
+            if current != *state {
+                changes.push(StateChange {
+                    token: *token,
+                    from: *state,
+                    to: current,
+                });
+                *state = current;
+            }
+        }
+
+        for change in &changes {
+            if change.to.is_terminal() {
+                // SRT drops closed sockets from the epoll by itself
+                drop(self.unwatch(change.token));
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+impl fmt::Debug for StateWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateWatcher")
+            .field("watched", &self.watched.len())
+            .finish()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// SRT Poll
+////////////////////////////////////////////////////////////////////////////////
+
+/// Send buffer levels (in bytes) between which a producer should pause.
+///
+/// Once the sending buffer of a socket registered with
+/// `Poll::register_watermark` exceeds `high`, the socket is no longer
+/// reported writable until the buffer drains to `low` or below, so a
+/// producer can slow down before SRT starts dropping packets.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Watermark {
+    low: usize,
+    high: usize,
+}
+
+impl Watermark {
+    /// Fails with `InvalidInput` if `low` is above `high`.
+    pub fn new(low: usize, high: usize) -> io::Result<Watermark> {
+        if low > high {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "low watermark above high watermark"));
+        }
+        Ok(Watermark {
+            low: low,
+            high: high,
+        })
+    }
+
+    pub fn low(&self) -> usize {
+        self.low
+    }
+
+    pub fn high(&self) -> usize {
+        self.high
+    }
+}
+
+struct Watermarked {
+    token: Token,
+    event: EventKind,
+    watermark: Watermark,
+    throttled: bool,
+}
+
+// Events only carry tokens, so each watermarked socket needs a token of
+// its own to be found again.
+struct Watermarks {
+    socks: HashMap<SRTSOCKET, Watermarked>,
+    tokens: HashMap<Token, SRTSOCKET>,
+}
+
+impl Watermarks {
+    fn check_token(&self, sock: SRTSOCKET, token: Token) -> io::Result<()> {
+        match self.tokens.get(&token) {
+            Some(&other) if other != sock => {
+                Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                                   "token already used by a watermarked socket"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn insert(&mut self, sock: SRTSOCKET, entry: Watermarked) {
+        self.remove(sock);
+        self.tokens.insert(entry.token, sock);
+        self.socks.insert(sock, entry);
+    }
+
+    fn remove(&mut self, sock: SRTSOCKET) -> Option<Watermarked> {
+        let entry = self.socks.remove(&sock)?;
+        self.tokens.remove(&entry.token);
+        Some(entry)
+    }
+}
+
+/// Polls for readiness events on all registered values.
+pub struct Poll {
+    poll: sys::Poll,
+    watermarks: Mutex<Watermarks>,
+}
+
+impl Poll {
+    /// Return a new `Poll` handle.
+    pub fn new() -> io::Result<Poll> {
+        Ok(Poll {
+            poll: sys::Poll::new()?,
+            watermarks: Mutex::new(Watermarks {
+                socks: HashMap::new(),
+                tokens: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Register an `AsSocket` instance with the `Poll` instance.
+    pub fn register<S: AsSocket>(
+        &self,
         socket: &S,
         token: Token,
         event: EventKind,
     ) -> io::Result<()> {
+        self.watermarks.lock().unwrap().remove(socket.as_socket().as_raw());
         self.poll.register(socket.as_socket(), token, event)
     }
 
+    /// Register an `AsSocket` instance whose writability also depends on
+    /// the level of its sending buffer (see `Watermark`).
+    ///
+    /// The token has to be unique among the watermarked sockets. A socket
+    /// found closed is dropped from the `Poll` and reported as an error
+    /// event.
+    pub fn register_watermark<S: AsSocket>(
+        &self,
+        socket: &S,
+        token: Token,
+        event: EventKind,
+        watermark: Watermark,
+    ) -> io::Result<()> {
+        let sock = socket.as_socket().as_raw();
+        let mut watermarks = self.watermarks.lock().unwrap();
+        watermarks.check_token(sock, token)?;
+        self.poll.register(socket.as_socket(), token, event)?;
+        watermarks.insert(
+            sock,
+            Watermarked {
+                token: token,
+                event: event,
+                watermark: watermark,
+                throttled: false,
+            },
+        );
+        Ok(())
+    }
+
     /// Re-register an `AsSocket` instance with the `Poll` instance.
     pub fn reregister<S: AsSocket>(
         &self,
@@ -293,15 +2153,448 @@ impl Poll {
         token: Token,
         event: EventKind,
     ) -> io::Result<()> {
-        self.poll.reregister(socket.as_socket(), token, event)
+        let sock = socket.as_socket().as_raw();
+        let mut watermarks = self.watermarks.lock().unwrap();
+        match watermarks.remove(sock) {
+            Some(mut entry) => {
+                if let Err(e) = watermarks.check_token(sock, token) {
+                    watermarks.insert(sock, entry);
+                    return Err(e);
+                }
+                entry.token = token;
+                entry.event = event;
+                let polled = if entry.throttled { unwritable(event) } else { event };
+                watermarks.insert(sock, entry);
+                self.poll.reregister(socket.as_socket(), token, polled)
+            }
+            None => self.poll.reregister(socket.as_socket(), token, event),
+        }
     }
 
     /// Deregister an `AsSocket` instance with the `Poll` instance.
     pub fn deregister<S: AsSocket>(&self, socket: &S) -> io::Result<()> {
+        self.watermarks.lock().unwrap().remove(socket.as_socket().as_raw());
         self.poll.deregister(socket.as_socket())
     }
 
+    // The stream reactor polls sockets it doesn't own for readiness, once
+    // the waiting task is gone it only knows them by id (see
+    // `stream::Handle::poll_ready`).
+
+    #[cfg(feature = "stream")]
+    pub(crate) fn register_socket(
+        &self,
+        socket: &Socket,
+        token: Token,
+        event: EventKind,
+    ) -> io::Result<()> {
+        self.poll.register(socket, token, event)
+    }
+
+    #[cfg(feature = "stream")]
+    pub(crate) fn reregister_socket(
+        &self,
+        socket: &Socket,
+        token: Token,
+        event: EventKind,
+    ) -> io::Result<()> {
+        self.poll.reregister(socket, token, event)
+    }
+
+    #[cfg(feature = "stream")]
+    pub(crate) fn reregister_raw(
+        &self,
+        sock: SRTSOCKET,
+        token: Token,
+        event: EventKind,
+    ) -> io::Result<()> {
+        self.poll.reregister_raw(sock, token, event)
+    }
+
+    #[cfg(feature = "stream")]
+    pub(crate) fn deregister_raw(&self, sock: SRTSOCKET) -> io::Result<()> {
+        self.poll.deregister_raw(sock)
+    }
+
     pub fn poll(&self, events: &mut Events, timeout: Option<Duration>) -> io::Result<usize> {
-        self.poll.poll(events, timeout)
+        // SRT has no event for a draining buffer, throttled sockets are
+        // checked at this interval instead
+        const CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+        let mut watermarks = self.watermarks.lock().unwrap();
+        if watermarks.socks.is_empty() {
+            drop(watermarks);
+            return self.poll.poll(events, timeout);
+        }
+
+        let throttled = watermarks.socks.values().any(|entry| entry.throttled);
+        let timeout = match timeout {
+            Some(timeout) if throttled => Some(cmp::min(timeout, CHECK_INTERVAL)),
+            None if throttled => Some(CHECK_INTERVAL),
+            timeout => timeout,
+        };
+
+        // don't hold the lock while waiting
+        drop(watermarks);
+        let mut polled = Events::with_capacity(events.capacity());
+        self.poll.poll(&mut polled, timeout)?;
+        watermarks = self.watermarks.lock().unwrap();
+
+        // a socket failing here must not fail polling all the others
+        let mut gone = Vec::new();
+        let mut ready = Events::with_capacity(polled.len());
+        for event in &polled {
+            let sock = match watermarks.tokens.get(&event.token()) {
+                Some(&sock) if event.kind().is_writable() => sock,
+                _ => {
+                    ready.push(event);
+                    continue;
+                }
+            };
+            let entry = watermarks.socks.get_mut(&sock).unwrap();
+
+            let bytes = match send_level(sock) {
+                Some(bytes) => bytes,
+                None => {
+                    gone.push(sock);
+                    continue;
+                }
+            };
+            if bytes > entry.watermark.high {
+                trace!("send buffer above high watermark ({} bytes)", bytes);
+                if self.poll.reregister_raw(sock, entry.token, unwritable(entry.event)).is_err() {
+                    gone.push(sock);
+                    continue;
+                }
+                entry.throttled = true;
+                let kind = unwritable(event.kind());
+                if !kind.is_empty() {
+                    ready.push(Event::new(event.token(), kind));
+                }
+            } else {
+                ready.push(event);
+            }
+        }
+
+        for (&sock, entry) in watermarks.socks.iter_mut() {
+            if !entry.throttled || gone.contains(&sock) {
+                continue;
+            }
+            let bytes = match send_level(sock) {
+                Some(bytes) => bytes,
+                None => {
+                    gone.push(sock);
+                    continue;
+                }
+            };
+            if bytes <= entry.watermark.low {
+                trace!("send buffer below low watermark ({} bytes)", bytes);
+                if self.poll.reregister_raw(sock, entry.token, entry.event).is_err() {
+                    gone.push(sock);
+                    continue;
+                }
+                entry.throttled = false;
+                if entry.event.is_writable() {
+                    ready.push(Event::new(entry.token, EventKind::writable()));
+                }
+            }
+        }
+
+        for sock in gone {
+            if let Some(entry) = watermarks.remove(sock) {
+                trace!("watermarked socket gone");
+                drop(self.poll.deregister_raw(sock));
+                ready.push(Event::new(entry.token, EventKind::error()));
+            }
+        }
+
+        let len = ready.len();
+        events.append(&mut ready);
+        Ok(len)
+    }
+}
+
+fn unwritable(kind: EventKind) -> EventKind {
+    kind & (EventKind::readable() | EventKind::error())
+}
+
+/// Bytes in the sending buffer of a socket, `None` once it is gone.
+fn send_level(sock: SRTSOCKET) -> Option<usize> {
+    match sys::sockstate(sock) {
+        SOCKSTATUS::SRTS_BROKEN
+        | SOCKSTATUS::SRTS_CLOSING
+        | SOCKSTATUS::SRTS_CLOSED
+        | SOCKSTATUS::SRTS_NONEXIST => None,
+        _ => sys::sndbuffer(sock).ok().map(|(_, bytes)| bytes),
+    }
+}
+
+/// Polls for readiness events on sockets spread over several SRT epoll
+/// instances (shards), each meant to be polled by its own thread.
+///
+/// `ShardedReactor` runs those threads. Otherwise they are the caller's:
+/// each calls `poll` with its shard in a loop, usually sharing the
+/// `ShardedPoll` through an `Arc`. Registering and deregistering is safe
+/// from any thread meanwhile.
+///
+/// A socket is placed on the least loaded shard when registered, and one
+/// socket is migrated from the most to the least loaded shard whenever a
+/// deregistration leaves them unbalanced. Tokens stay the same wherever a
+/// socket lives; a migrated socket's event may still be reported by its
+/// old shard if it was already pending there.
+pub struct ShardedPoll {
+    shards: Vec<Shard>,
+    socks: Mutex<HashMap<SRTSOCKET, Placement>>,
+}
+
+struct Shard {
+    poll: sys::Poll,
+    load: AtomicUsize,
+}
+
+#[derive(Copy, Clone)]
+struct Placement {
+    shard: usize,
+    token: Token,
+    event: EventKind,
+}
+
+impl ShardedPoll {
+    /// Return a new `ShardedPoll` with the given number of shards.
+    pub fn new(shards: usize) -> io::Result<ShardedPoll> {
+        if shards == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "at least one shard is needed"));
+        }
+        let shards = (0..shards)
+            .map(|_| {
+                Ok(Shard {
+                    poll: sys::Poll::new()?,
+                    load: AtomicUsize::new(0),
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(ShardedPoll {
+            shards: shards,
+            socks: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns the number of shards.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the number of sockets registered on a shard.
+    pub fn load(&self, shard: usize) -> usize {
+        self.shards[shard].load.load(Ordering::SeqCst)
+    }
+
+    /// Returns the shard a socket is currently registered on.
+    pub fn shard_of<S: AsSocket>(&self, socket: &S) -> Option<usize> {
+        let socks = self.socks.lock().unwrap();
+        socks.get(&socket.as_socket().as_raw()).map(|placement| placement.shard)
+    }
+
+    /// Register an `AsSocket` instance on the least loaded shard, returns
+    /// the shard.
+    pub fn register<S: AsSocket>(
+        &self,
+        socket: &S,
+        token: Token,
+        event: EventKind,
+    ) -> io::Result<usize> {
+        let sock = socket.as_socket().as_raw();
+        let mut socks = self.socks.lock().unwrap();
+        if socks.contains_key(&sock) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                                      "socket already registered"));
+        }
+
+        let shard = self.least_loaded();
+        self.shards[shard].poll.register_raw(sock, token, event)?;
+        self.shards[shard].load.fetch_add(1, Ordering::SeqCst);
+        socks.insert(sock, Placement {
+            shard: shard,
+            token: token,
+            event: event,
+        });
+        Ok(shard)
+    }
+
+    /// Re-register an `AsSocket` instance on its current shard.
+    pub fn reregister<S: AsSocket>(
+        &self,
+        socket: &S,
+        token: Token,
+        event: EventKind,
+    ) -> io::Result<()> {
+        let sock = socket.as_socket().as_raw();
+        let mut socks = self.socks.lock().unwrap();
+        let placement = socks.get_mut(&sock).ok_or_else(not_registered)?;
+        self.shards[placement.shard].poll.reregister_raw(sock, token, event)?;
+        placement.token = token;
+        placement.event = event;
+        Ok(())
+    }
+
+    /// Deregister an `AsSocket` instance, rebalancing the shards if needed.
+    pub fn deregister<S: AsSocket>(&self, socket: &S) -> io::Result<()> {
+        let sock = socket.as_socket().as_raw();
+        let mut socks = self.socks.lock().unwrap();
+        let placement = socks.remove(&sock).ok_or_else(not_registered)?;
+        self.shards[placement.shard].load.fetch_sub(1, Ordering::SeqCst);
+        self.shards[placement.shard].poll.deregister_raw(sock)?;
+
+        self.rebalance(&mut socks)
+    }
+
+    /// Waits for events on one shard, see `Poll::poll`.
+    pub fn poll(
+        &self,
+        shard: usize,
+        events: &mut Events,
+        timeout: Option<Duration>,
+    ) -> io::Result<usize> {
+        self.shards[shard].poll.poll(events, timeout)
+    }
+
+    fn least_loaded(&self) -> usize {
+        (0..self.shards.len()).min_by_key(|&shard| self.load(shard)).unwrap()
+    }
+
+    fn most_loaded(&self) -> usize {
+        (0..self.shards.len()).max_by_key(|&shard| self.load(shard)).unwrap()
+    }
+
+    fn rebalance(&self, socks: &mut HashMap<SRTSOCKET, Placement>) -> io::Result<()> {
+        let (from, to) = (self.most_loaded(), self.least_loaded());
+        if self.load(from) <= self.load(to) + 1 {
+            return Ok(());
+        }
+
+        let (&sock, placement) = match socks.iter_mut().find(|(_, p)| p.shard == from) {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+        trace!("migrating socket {} from shard {} to {}", sock, from, to);
+        // SRT epoll is level triggered, a pending event is reported again
+        // by the new shard
+        self.shards[from].poll.deregister_raw(sock)?;
+        match self.shards[to].poll.register_raw(sock, placement.token, placement.event) {
+            Ok(()) => {
+                self.shards[from].load.fetch_sub(1, Ordering::SeqCst);
+                self.shards[to].load.fetch_add(1, Ordering::SeqCst);
+                placement.shard = to;
+                Ok(())
+            }
+            Err(e) => {
+                // the deregistration itself went fine, stay where we were
+                warn!("cannot migrate socket {}: {}", sock, e);
+                self.shards[from].poll.register_raw(sock, placement.token, placement.event)
+            }
+        }
+    }
+}
+
+fn not_registered() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "socket not registered")
+}
+
+/// Runs a `ShardedPoll` with one thread per shard, handing each event to
+/// a handler on the thread of the shard reporting it.
+///
+/// Sockets are registered through `poll`, from any thread. Handlers of
+/// different shards run concurrently, so a socket migrated meanwhile may
+/// have its events handled by two threads at once.
+pub struct ShardedReactor {
+    poll: Arc<ShardedPoll>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<io::Result<()>>>,
+}
+
+impl ShardedReactor {
+    /// Starts a thread for each shard of a new `ShardedPoll`, calling
+    /// `handler` with the shard and each of its events.
+    pub fn new<H>(shards: usize, handler: H) -> io::Result<ShardedReactor>
+    where
+        H: Fn(usize, Event) + Send + Sync + 'static,
+    {
+        // how often the threads look for a shutdown
+        const TIMEOUT: Duration = Duration::from_millis(100);
+        const EVENTS: usize = 1024;
+
+        let poll = Arc::new(ShardedPoll::new(shards)?);
+        let stop = Arc::new(AtomicBool::new(false));
+        let handler = Arc::new(handler);
+        let mut reactor = ShardedReactor {
+            poll: poll,
+            stop: stop,
+            threads: Vec::with_capacity(shards),
+        };
+        for shard in 0..shards {
+            let (poll, stop) = (reactor.poll.clone(), reactor.stop.clone());
+            let handler = handler.clone();
+            let thread = thread::Builder::new()
+                .name(format!("srt-shard-{}", shard))
+                .spawn(move || {
+                    let mut events = Events::with_capacity(EVENTS);
+                    while !stop.load(Ordering::SeqCst) {
+                        events.clear();
+                        if let Err(e) = poll.poll(shard, &mut events, Some(TIMEOUT)) {
+                            error!("srt shard {} failed: {}", shard, e);
+                            return Err(e);
+                        }
+                        for event in &events {
+                            handler(shard, event);
+                        }
+                    }
+                    Ok(())
+                })?;
+            // dropping the reactor stops the threads started so far
+            reactor.threads.push(thread);
+        }
+        Ok(reactor)
+    }
+
+    /// Returns the poll to register sockets with.
+    pub fn poll(&self) -> &ShardedPoll {
+        &self.poll
+    }
+
+    /// Stops the threads and waits for them to finish. Returns the first
+    /// failure of the threads, `Err` if one panicked.
+    pub fn shutdown(mut self) -> thread::Result<io::Result<()>> {
+        self.stop.store(true, Ordering::SeqCst);
+        let mut res = Ok(Ok(()));
+        for thread in self.threads.drain(..) {
+            match (&res, thread.join()) {
+                (&Err(_), _) => {}
+                (_, Err(panic)) => res = Err(panic),
+                (&Ok(Ok(())), Ok(Err(e))) => res = Ok(Err(e)),
+                _ => {}
+            }
+        }
+        res
+    }
+}
+
+impl Drop for ShardedReactor {
+    fn drop(&mut self) {
+        // the threads exit on their own
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod fec_tests {
+    use super::*;
+
+    #[test]
+    fn negotiated_fec() {
+        // keys unknown to the bindings are skipped in what peers negotiated
+        assert_eq!(parse_fec("fec,cols:10,future:1", true).unwrap(), Fec::new(10));
+        assert!(parse_fec("fec,cols:10,future:1", false).is_err());
     }
 }
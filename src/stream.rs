@@ -1,9 +1,14 @@
+use bytes::{Bytes, BytesMut};
 use futures::{
     channel,
-    stream,
+    sink::Sink,
+    stream::{self, StreamExt},
+    task::{waker, ArcWake, AtomicWaker},
 };
+use libsrt_sys::{self as sys, Socket, SOCKSTATUS, SRTSOCKET};
 use slab::Slab;
 use std::{
+    collections::HashMap,
     future::Future,
     io::{self, Read, Write},
     net::SocketAddr,
@@ -11,66 +16,214 @@ use std::{
     sync::{
         self,
         Arc,
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
     },
     task::{
         self,
         Context,
+        Waker,
     },
+    cmp,
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use crate::net::{
     self,
     TRANSTYPE,
+    AsSocket,
+    Congestion,
     Bind,
+    Connect,
     EventKind,
     Events,
     Listener,
+    MsgInfo,
     Poll,
     Stream,
     Token,
 };
 
 const MSG_PLSIZE: usize = 32;
+/// How long starting a reactor thread waits for the channel to its event
+/// loop to connect
+const CHANNEL_TIMEOUT: Duration = Duration::from_secs(5);
+/// Tasks and events an event loop makes room for at first
+const INITIAL_TASKS: usize = 1000;
+/// How long an idle event loop sleeps before looking at its tasks again
+const LOOP_TIMEOUT: Duration = Duration::from_secs(1);
 
 ////////////////////////////////////////////////////////////////////////////////
-// Builder
+// Reactor
 ////////////////////////////////////////////////////////////////////////////////
-pub struct Builder {
-    tx: Sender<Message>,
-    cnt: Arc<AtomicUsize>,
-}
 
-enum Message {
-    Connecting(SocketAddr, channel::oneshot::Sender<Stream>),
-    Listening(Listener, channel::mpsc::Sender<Stream>),
-    Done,
+/// Handle to a SRT reactor, the threads polling the sockets of
+/// connections and listeners in progress.
+///
+/// The futures it completes only rely on `std::task` wakers, so a reactor
+/// can serve async-std, smol and tokio tasks alike. Most code should share
+/// the process-wide reactor (see `Handle::global`) instead of starting its
+/// own.
+#[derive(Clone)]
+pub struct Handle {
+    inner: Arc<Reactor>,
 }
 
-struct Sender<T> {
-    tx: sync::mpsc::Sender<T>,
-    inner: Arc<Channel>,
+struct Reactor {
+    workers: Vec<Worker>,
 }
 
-struct Receiver<T> {
-    rx: sync::mpsc::Receiver<T>,
-    inner: Arc<Channel>,
+struct Worker {
+    tx: Sender<Message>,
+    /// Number of connections and listeners the thread is polling
+    load: Arc<AtomicUsize>,
+    readiness: Arc<Readiness>,
+    thread: sync::Mutex<Option<thread::JoinHandle<io::Result<()>>>>,
 }
 
-struct Channel {
-    tx: Stream,
-    rx: Stream,
+static GLOBAL: sync::Mutex<Option<Handle>> = sync::Mutex::new(None);
+static GLOBAL_THREADS: AtomicUsize = AtomicUsize::new(1);
+
+impl Handle {
+    /// Starts a new reactor with the given number of threads.
+    pub fn new(threads: usize) -> io::Result<Handle> {
+        if threads == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "a reactor needs at least one thread"));
+        }
+        let workers = (0..threads)
+            .map(|i| Worker::spawn(format!("srt-reactor-{}", i)))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Handle {
+            inner: Arc::new(Reactor {
+                workers: workers,
+            }),
+        })
+    }
+
+    /// Returns the process-wide reactor, starting it on first use (or
+    /// again after it was shut down).
+    pub fn global() -> io::Result<Handle> {
+        let mut global = GLOBAL.lock().unwrap_or_else(|e| e.into_inner());
+        match *global {
+            Some(ref handle) if handle.is_running() => Ok(handle.clone()),
+            _ => {
+                let handle = Handle::new(GLOBAL_THREADS.load(Ordering::SeqCst))?;
+                *global = Some(handle.clone());
+                Ok(handle)
+            }
+        }
+    }
+
+    /// Sets the number of threads the process-wide reactor is started
+    /// with (1 by default); has no effect on a running one.
+    pub fn set_global_threads(threads: usize) {
+        GLOBAL_THREADS.store(cmp::max(threads, 1), Ordering::SeqCst);
+    }
+
+    /// Returns the number of reactor threads.
+    pub fn threads(&self) -> usize {
+        self.inner.workers.len()
+    }
+
+    /// Returns whether all reactor threads are still running.
+    ///
+    /// Once one stopped, new connections and listeners handed to it fail
+    /// at once.
+    pub fn is_running(&self) -> bool {
+        self.inner.workers.iter().all(Worker::is_running)
+    }
+
+    /// Stops the reactor (for all clones of the handle) and waits for its
+    /// threads to finish.
+    ///
+    /// Pending connections and listeners fail with `ConnectionAborted`, as
+    /// do tasks waiting for a socket to become ready. Returns the first failure of the threads, `Err` if one panicked.
+    pub fn shutdown(&self) -> thread::Result<io::Result<()>> {
+        for worker in &self.inner.workers {
+            drop(worker.tx.send(Message::Shutdown));
+        }
+        self.join()
+    }
+
+    /// Stops the reactor once the pending connections completed and all
+    /// `Incoming`s are dropped, waiting at most `timeout` before stopping
+    /// it as `shutdown` does.
+    ///
+    /// New connections and listeners fail at once in the meantime.
+    pub fn shutdown_timeout(&self, timeout: Duration) -> thread::Result<io::Result<()>> {
+        const INTERVAL: Duration = Duration::from_millis(10);
+
+        for worker in &self.inner.workers {
+            drop(worker.tx.send(Message::Done));
+        }
+        let start = Instant::now();
+        while self.is_running() && start.elapsed() < timeout {
+            thread::sleep(INTERVAL);
+        }
+        self.shutdown()
+    }
+
+    fn join(&self) -> thread::Result<io::Result<()>> {
+        let mut res = Ok(Ok(()));
+        for worker in &self.inner.workers {
+            let thread = worker.thread.lock().unwrap_or_else(|e| e.into_inner()).take();
+            let joined = match thread {
+                Some(thread) => thread.join(),
+                None => continue,
+            };
+            match (&res, joined) {
+                (&Err(_), _) => {}
+                (_, Err(panic)) => res = Err(panic),
+                (&Ok(Ok(())), Ok(Err(e))) => res = Ok(Err(e)),
+                _ => {}
+            }
+        }
+        res
+    }
+
+    /// Picks the least loaded thread to hand new work to.
+    fn worker(&self) -> &Worker {
+        let worker = self.inner.workers
+            .iter()
+            .min_by_key(|worker| worker.load.load(Ordering::SeqCst))
+            .unwrap();
+        // until the thread takes count itself
+        worker.load.fetch_add(1, Ordering::SeqCst);
+        worker
+    }
+
+    /// Polls a nonblocking socket for readiness on behalf of the task of
+    /// `cx` (see `net::Stream::poll_read_ready`).
+    ///
+    /// A socket is always served by the same thread. Fails once the
+    /// reactor stopped, waking up the tasks still waiting.
+    pub(crate) fn poll_ready(
+        &self,
+        socket: &Socket,
+        kind: EventKind,
+        cx: &mut Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let workers = &self.inner.workers;
+        let worker = &workers[socket.as_raw() as u32 as usize % workers.len()];
+        if !worker.is_running() {
+            return task::Poll::Ready(Err(stopped()));
+        }
+        worker.readiness.poll_ready(socket, kind, cx)
+    }
 }
 
-enum Task {
-    Message(),
-    Connecting(Stream, channel::oneshot::Sender<Stream>),
-    Listening(Listener, channel::mpsc::Sender<Stream>),
+impl Drop for Reactor {
+    fn drop(&mut self) {
+        // let the threads finish their pending work and exit
+        for worker in &self.workers {
+            drop(worker.tx.send(Message::Done));
+        }
+    }
 }
 
-impl Builder {
-    pub fn new() -> Result<Builder, io::Error> {
+impl Worker {
+    fn spawn(name: String) -> io::Result<Worker> {
         const LISTEN_TOKEN: Token = Token(0);
         const CONN1_TOKEN: Token = Token(1);
 
@@ -95,31 +248,42 @@ impl Builder {
         poll.register(&conn1, CONN1_TOKEN,
                       EventKind::writable() | EventKind::error())?;
 
-        // XXX infinite waiting
+        let deadline = Instant::now() + CHANNEL_TIMEOUT;
         let mut accepted = None;
         let mut connected = false;
         let conn2 = loop {
+            match accepted.take() {
+                Some(conn2) if connected => break conn2,
+                conn2 => accepted = conn2,
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                          "srt event loop channel not connected"));
+            }
             events.clear();
-            poll.poll(&mut events, Some(Duration::from_millis(500)))?; // XXX
+            poll.poll(&mut events, Some(deadline - now))?;
             for event in &events {
                 match event.token() {
                     LISTEN_TOKEN => {
                         trace!("channel accepted");
                         accepted = Some(listener.accept()?.0);
-                    },
+                    }
+                    CONN1_TOKEN if event.kind().is_error() => {
+                        return Err(io::Error::new(io::ErrorKind::ConnectionRefused,
+                                                  "srt event loop channel refused"));
+                    }
                     CONN1_TOKEN => {
                         trace!("channel connected");
                         connected = true;
-                    },
-                    _ => unreachable!()
+                    }
+                    // nothing else is registered
+                    _ => {}
                 }
             }
-
-            if connected && accepted.is_some() {
-                break accepted.unwrap();
-            }
         };
-        
+
         drop(listener);
 
         let inner = Arc::new(Channel {
@@ -132,181 +296,799 @@ impl Builder {
         let tx2 = tx.clone();
         let rx = Receiver { rx: rx, inner: inner };
 
-        thread::spawn(|| {
-            run(tx2, rx);
-        });
+        let load = Arc::new(AtomicUsize::new(0));
+        let load2 = load.clone();
+        let readiness = Arc::new(Readiness::new(Poll::new()?));
+        let readiness2 = readiness.clone();
+        let thread = thread::Builder::new()
+            .name(name)
+            .spawn(move || {
+                let res = run(&tx2, &rx, &load2, &readiness2);
+                if let Err(ref e) = res {
+                    error!("srt event loop failed: {}", e);
+                }
+                res
+            })?;
 
-        Ok(Builder {
+        Ok(Worker {
             tx: tx,
-            cnt: Arc::new(AtomicUsize::new(1)),
+            load: load,
+            readiness: readiness,
+            thread: sync::Mutex::new(Some(thread)),
         })
     }
 
+    fn is_running(&self) -> bool {
+        match *self.thread.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(ref thread) => !thread.is_finished(),
+            None => false,
+        }
+    }
+}
+
+enum Message {
+    Connecting(
+        SocketAddr,
+        Arc<net::Builder>,
+        channel::oneshot::Sender<io::Result<Stream>>,
+    ),
+    Listening(
+        Listener,
+        Arc<net::Builder>,
+        channel::mpsc::Sender<io::Result<Stream>>,
+    ),
+    /// An `Incoming` has room again, the listener at the index can resume
+    Resume(usize),
+    /// A `Connecting` or `Incoming` was dropped before completing
+    Canceled,
+    Done,
+    Shutdown,
+}
+
+struct Sender<T> {
+    tx: sync::mpsc::Sender<T>,
+    inner: Arc<Channel>,
+}
+
+struct Receiver<T> {
+    rx: sync::mpsc::Receiver<T>,
+    inner: Arc<Channel>,
+}
+
+struct Channel {
+    tx: Stream,
+    rx: Stream,
+}
+
+enum Task {
+    Message(),
+    Connecting(Stream, SocketAddr, channel::oneshot::Sender<io::Result<Stream>>),
+    Listening(Listening),
+}
+
+struct Listening {
+    listener: Listener,
+    options: Arc<net::Builder>,
+    incoming: channel::mpsc::Sender<io::Result<Stream>>,
+    waker: Waker,
+    paused: bool,
+}
+
+/// Wakes the event loop up once an `Incoming` consumer made room.
+struct Resume {
+    index: usize,
+    tx: sync::Mutex<Sender<Message>>,
+}
+
+/// Marks the tokens of sockets polled for readiness, the rest of the
+/// token is their index in `Interests::entries`.
+const READINESS_TOKEN: usize = 1 << (usize::BITS - 1);
+
+/// Tasks waiting for nonblocking sockets to become readable or writable.
+///
+/// SRT sockets have no kernel fd an executor could watch, so the worker
+/// thread owning `poll` polls them along with its own tasks and wakes the
+/// waiting tasks up.
+struct Readiness {
+    poll: Poll,
+    state: sync::Mutex<Interests>,
+}
+
+struct Interests {
+    /// Index of each socket's entry
+    socks: HashMap<SRTSOCKET, usize>,
+    entries: Slab<Interest>,
+    last_sweep: Instant,
+    stopped: bool,
+}
+
+struct Interest {
+    sock: SRTSOCKET,
+    /// Readiness reported by the poll and not consumed yet
+    ready: EventKind,
+    reader: Option<Waker>,
+    writer: Option<Waker>,
+    armed: bool,
+}
+
+impl Readiness {
+    fn new(poll: Poll) -> Readiness {
+        Readiness {
+            poll: poll,
+            state: sync::Mutex::new(Interests {
+                socks: HashMap::new(),
+                entries: Slab::new(),
+                last_sweep: Instant::now(),
+                stopped: false,
+            }),
+        }
+    }
+
+    fn poll_ready(
+        &self,
+        socket: &Socket,
+        kind: EventKind,
+        cx: &mut Context<'_>,
+    ) -> task::Poll<io::Result<()>> {
+        let sock = socket.as_raw();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let state = &mut *state;
+        if state.stopped {
+            return task::Poll::Ready(Err(stopped()));
+        }
+        let entries = &mut state.entries;
+        let index = *state.socks.entry(sock).or_insert_with(|| {
+            entries.insert(Interest {
+                sock: sock,
+                ready: EventKind::empty(),
+                reader: None,
+                writer: None,
+                armed: false,
+            })
+        });
+        let interest = &mut state.entries[index];
+
+        // an error stays pending for both directions
+        if !(interest.ready & (kind | EventKind::error())).is_empty() {
+            interest.ready = EventKind::from_int(interest.ready.as_int() & !kind.as_int());
+            return task::Poll::Ready(Ok(()));
+        }
+
+        let waker = Some(cx.waker().clone());
+        if kind.is_readable() {
+            interest.reader = waker;
+        } else {
+            interest.writer = waker;
+        }
+        let token = Token(READINESS_TOKEN | index);
+        let res = if interest.armed {
+            self.poll.reregister_socket(socket, token, interest.wanted())
+        } else {
+            self.poll.register_socket(socket, token, interest.wanted())
+        };
+        if let Err(e) = res {
+            return task::Poll::Ready(Err(e));
+        }
+        interest.armed = true;
+        task::Poll::Pending
+    }
+
+    /// Wakes up the tasks waiting for the event of the entry at `index`.
+    fn handle(&self, index: usize, kind: EventKind) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let interest = match state.entries.get_mut(index) {
+            Some(interest) => interest,
+            // forgotten in the meantime
+            None => return,
+        };
+        interest.ready |= kind;
+        interest.wake(kind);
+
+        // SRT epoll is level triggered, only keep polling what is still
+        // waited for
+        let token = Token(READINESS_TOKEN | index);
+        if interest.reader.is_some() || interest.writer.is_some() {
+            drop(self.poll.reregister_raw(interest.sock, token, interest.wanted()));
+        } else if interest.armed {
+            drop(self.poll.deregister_raw(interest.sock));
+            interest.armed = false;
+        }
+    }
+
+    /// Forgets the sockets closed in the meantime, checking them at most
+    /// once per `SWEEP_INTERVAL`.
+    fn sweep(&self) {
+        const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.last_sweep.elapsed() < SWEEP_INTERVAL {
+            return;
+        }
+        let state = &mut *state;
+        state.last_sweep = Instant::now();
+
+        let socks = &mut state.socks;
+        state.entries.retain(|_, interest| {
+            match sys::sockstate(interest.sock) {
+                SOCKSTATUS::SRTS_CLOSED | SOCKSTATUS::SRTS_NONEXIST => {}
+                _ => return true,
+            }
+            // SRT already removed the socket from the poll
+            interest.wake(EventKind::all());
+            socks.remove(&interest.sock);
+            false
+        });
+    }
+
+    /// Wakes up all waiting tasks for good, the thread is exiting.
+    fn stop(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.stopped = true;
+        state.socks.clear();
+        for mut interest in state.entries.drain() {
+            interest.wake(EventKind::all());
+        }
+    }
+}
+
+impl Interest {
+    fn wanted(&self) -> EventKind {
+        let mut event = EventKind::error();
+        if self.reader.is_some() {
+            event |= EventKind::readable();
+        }
+        if self.writer.is_some() {
+            event |= EventKind::writable();
+        }
+        event
+    }
+
+    fn wake(&mut self, kind: EventKind) {
+        if kind.is_readable() || kind.is_error() {
+            if let Some(reader) = self.reader.take() {
+                reader.wake();
+            }
+        }
+        if kind.is_writable() || kind.is_error() {
+            if let Some(writer) = self.writer.take() {
+                writer.wake();
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Builder
+////////////////////////////////////////////////////////////////////////////////
+#[derive(Clone)]
+pub struct Builder {
+    handle: Handle,
+    options: Arc<net::Builder>,
+    backlog: usize,
+    incoming_capacity: usize,
+    connect_timeout: Option<Duration>,
+    observer: Option<Arc<dyn net::Observer>>,
+}
+
+impl Builder {
+    /// Returns a builder using the process-wide reactor.
+    pub fn new() -> Result<Builder, io::Error> {
+        Ok(Builder::with_handle(Handle::global()?))
+    }
+
+    /// Returns a builder using the given reactor.
+    pub fn with_handle(handle: Handle) -> Builder {
+        Builder {
+            handle: handle,
+            options: Arc::new(net::Builder::new().nonblocking(true)),
+            backlog: 128,
+            incoming_capacity: 128,
+            connect_timeout: None,
+            observer: None,
+        }
+    }
+
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Options applied to outgoing connections, to listeners created by
+    /// `bind` and to accepted connections, so synchronous and asynchronous
+    /// code can share one configuration
+    ///
+    /// Sockets are always made nonblocking, and the backlog is the one of
+    /// this builder.
+    pub fn options(mut self, options: net::Builder) -> Self {
+        self.options = Arc::new(options.nonblocking(true));
+        self
+    }
+
+    /// Maximum number of connections waiting in a listener's backlog
+    /// (see `bind`)
+    pub fn backlog(mut self, backlog: usize) -> Self {
+        self.backlog = backlog;
+        self
+    }
+
+    /// How long `connect` waits for the handshake to complete before
+    /// failing with `TimedOut` (3 seconds by default)
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Number of accepted connections an `Incoming` buffers (see `listen`)
+    ///
+    /// Once it is full, the listener is not accepted from until the
+    /// consumer catches up, so further connections wait in the backlog.
+    pub fn incoming_capacity(mut self, capacity: usize) -> Self {
+        self.incoming_capacity = capacity;
+        self
+    }
+
+    /// Observer notified about the connections made by `connect` and
+    /// accepted from listeners created by `bind` (see `net::Observer`)
+    pub fn observer(mut self, observer: Arc<dyn net::Observer>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
     pub fn connect(&self, addr: SocketAddr) -> Connecting {
         let (tx, rx) = channel::oneshot::channel();
-        self.tx.send(Message::Connecting(addr, tx));
-        Connecting { inner: rx }
+        let options = match (self.connect_timeout, &self.observer) {
+            (None, None) => self.options.clone(),
+            (timeout, observer) => {
+                let mut options = (*self.options).clone();
+                if let Some(timeout) = timeout {
+                    options = options.connect_timeout(timeout);
+                }
+                if let Some(observer) = observer {
+                    options = options.observer(observer.clone());
+                }
+                Arc::new(options)
+            }
+        };
+        let worker = self.handle.worker();
+        if let Err(msg) = worker.tx.send(Message::Connecting(addr, options, tx)) {
+            msg.fail(stopped());
+        }
+        Connecting { inner: rx, tx: worker.tx.clone() }
     }
 
     pub fn bind(&self, addr: &SocketAddr) -> io::Result<Listener> {
-        net::Builder::new().nonblocking(true).bind(addr)
+        let mut options = (*self.options).clone()
+            .nonblocking(true)
+            .backlog(self.backlog);
+        if let Some(ref observer) = self.observer {
+            options = options.observer(observer.clone());
+        }
+        options.bind(addr)
     }
 
     pub fn listen(&self, listener: Listener) -> Incoming {
-        let (tx, rx) = channel::mpsc::channel(1000); // XXX
-        self.tx.send(Message::Listening(listener, tx));
-        Incoming { inner: rx }
+        // the channel holds one more item per sender
+        let capacity = self.incoming_capacity.saturating_sub(1);
+        let (tx, rx) = channel::mpsc::channel(capacity);
+        let msg = Message::Listening(listener, self.options.clone(), tx);
+        let worker = self.handle.worker();
+        if let Err(msg) = worker.tx.send(msg) {
+            msg.fail(stopped());
+        }
+        Incoming { inner: rx, tx: worker.tx.clone() }
+    }
+
+    /// Returns whether the reactor is still running (see
+    /// `Handle::is_running`).
+    pub fn is_running(&self) -> bool {
+        self.handle.is_running()
+    }
+
+    /// Stops the reactor (see `Handle::shutdown`); this affects all users
+    /// of the same handle, the process-wide one included.
+    pub fn shutdown(self) -> thread::Result<io::Result<()>> {
+        self.handle.shutdown()
     }
 }
 
-impl Clone for Builder {
-    fn clone(&self) -> Builder {
-        self.cnt.fetch_add(1, Ordering::SeqCst);
-        Builder {
-            tx: self.tx.clone(),
-            cnt: self.cnt.clone(),
+fn stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "srt event loop stopped")
+}
+
+impl Message {
+    /// Delivers `err` to whoever waits for the message to be handled.
+    fn fail(self, err: io::Error) {
+        match self {
+            Message::Connecting(_addr, _options, complete) => {
+                drop(complete.send(Err(err)));
+            }
+            Message::Listening(_listener, _options, mut incoming) => {
+                drop(incoming.try_send(Err(err)));
+            }
+            Message::Resume(_)
+            | Message::Canceled
+            | Message::Done
+            | Message::Shutdown => {}
         }
     }
 }
 
-impl Drop for Builder {
-    fn drop(&mut self) {
-        if self.cnt.fetch_sub(1, Ordering::SeqCst) == 1 {
-            self.tx.send(Message::Done);
+impl Task {
+    /// Delivers `err` to whoever waits for the task to complete.
+    fn fail(self, err: io::Error) {
+        match self {
+            Task::Message() => {}
+            Task::Connecting(_stream, _addr, complete) => {
+                drop(complete.send(Err(err)));
+            }
+            Task::Listening(mut listening) => {
+                drop(listening.incoming.try_send(Err(err)));
+            }
         }
     }
 }
 
+/// Work left over from handling messages, done once all events of a turn
+/// are handled.
+#[derive(Default)]
+struct Pending {
+    resumed: Vec<usize>,
+    canceled: bool,
+}
+
+enum State {
+    Running,
+    Done,
+    Shutdown,
+}
+
 fn enqueue(
+    tx: &Sender<Message>,
     rx: &Receiver<Message>,
     poll: &Poll,
     tasks: &mut Slab<Task>,
-    done: &mut bool
-) {
-    rx.drain();
+    state: &mut State,
+    pending: &mut Pending,
+) -> io::Result<()> {
+    rx.drain()?;
     trace!("looking for some messages");
     while let Some(msg) = rx.recv() {
+        // no new work once shutting down
+        let msg = match (&*state, msg) {
+            (&State::Running, msg) => msg,
+            (_, msg @ Message::Connecting(..)) | (_, msg @ Message::Listening(..)) => {
+                msg.fail(stopped());
+                continue;
+            }
+            (_, msg) => msg,
+        };
         match msg {
             Message::Done => {
                 trace!("done");
-                *done = true;
+                if let State::Running = *state {
+                    *state = State::Done;
+                }
+            }
+            Message::Shutdown => {
+                trace!("shutdown");
+                *state = State::Shutdown;
             }
-            Message::Connecting(addr, complete) => {
+            Message::Resume(index) => {
+                trace!("resuming listener {}", index);
+                pending.resumed.push(index);
+            }
+            Message::Canceled => {
+                trace!("canceled");
+                pending.canceled = true;
+            }
+            Message::Connecting(addr, options, complete) => {
                 trace!("connecting to {}", addr);
-                // XXX check (tasks.len() == tasks.capacity())
-                let stream = net::Builder::new()
-                    .nonblocking(true)
-                    .connect(&addr).unwrap(); // XXX
-                let task = Task::Connecting(stream, complete);
-                let index = tasks.insert(task);
-                match tasks.get(index).unwrap() { // XXX
-                    Task::Connecting(stream, _complete) => {
-                        poll.register(&stream, Token(index),
-                                      EventKind::writable()).unwrap(); // XXX
+                let stream = match options.connect(&addr) {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        drop(complete.send(Err(e)));
+                        continue;
+                    }
+                };
+                let entry = tasks.vacant_entry();
+                match poll.register(&stream, Token(entry.key()),
+                                    EventKind::writable() | EventKind::error()) {
+                    Ok(()) => {
+                        entry.insert(Task::Connecting(stream, addr, complete));
+                    }
+                    Err(e) => {
+                        drop(complete.send(Err(e)));
                     }
-                    _ => unreachable!()
                 }
             }
-            Message::Listening(listener, accept) => {
-                trace!("listening to {}", listener.local_addr().unwrap());
-                // XXX check (tasks.len() == tasks.capacity())
-                let task = Task::Listening(listener, accept);
-                let index = tasks.insert(task);
-                match tasks.get(index).unwrap() { // XXX
-                    Task::Listening(listener, _accept) => {
-                        poll.register(&listener, Token(index),
-                                      EventKind::readable()).unwrap(); // XXX
+            Message::Listening(listener, options, mut incoming) => {
+                trace!("listening to {:?}", listener.local_addr());
+                let entry = tasks.vacant_entry();
+                match poll.register(&listener, Token(entry.key()),
+                                    EventKind::readable() | EventKind::error()) {
+                    Ok(()) => {
+                        let resume = Resume {
+                            index: entry.key(),
+                            tx: sync::Mutex::new(tx.clone()),
+                        };
+                        entry.insert(Task::Listening(Listening {
+                            listener: listener,
+                            options: options,
+                            incoming: incoming,
+                            waker: waker(Arc::new(resume)),
+                            paused: false,
+                        }));
+                    }
+                    Err(e) => {
+                        drop(incoming.try_send(Err(e)));
                     }
-                    _ => unreachable!()
                 }
             }
         }
     }
+    Ok(())
 }
 
-fn accept(listener: &Listener, incoming: &mut channel::mpsc::Sender<Stream>) {
-    loop {
-        match listener.accept() {
-            Ok((stream, peer_addr)) => {
-                let stream = net::Builder::new()
-                    .nonblocking(true)
-                    .accept(stream).unwrap();
-                trace!("connection established from {}", peer_addr);
-                let _t = incoming.try_send(stream); // XXX
+impl ArcWake for Resume {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        if let Ok(tx) = arc_self.tx.lock() {
+            drop(tx.send(Message::Resume(arc_self.index)));
+        }
+    }
+}
+
+/// Completes a nonblocking connect once the socket reported an event.
+fn connected(stream: &Stream) -> io::Result<()> {
+    match stream.state() {
+        net::SocketState::Connected => Ok(()),
+        _ => match stream.reject_reason() {
+            net::RejectReason::Timeout => {
+                Err(io::Error::new(io::ErrorKind::TimedOut, "connection timed out"))
             }
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                break;
+            reason => Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason)),
+        },
+    }
+}
+
+impl Listening {
+    /// Accepts pending connections while the `Incoming` has room, returns
+    /// false once the listener is of no further use.
+    ///
+    /// A full `Incoming` stops the listener from being polled for
+    /// readability until the consumer wakes it up again (see `Resume`).
+    fn accept(&mut self, poll: &Poll, token: Token) -> bool {
+        match self.try_accept(poll, token) {
+            Ok(more) => more,
+            Err(e) => {
+                drop(self.incoming.try_send(Err(e)));
+                false
             }
-            Err(_e) => {
-                break; // XXX
+        }
+    }
+
+    fn try_accept(&mut self, poll: &Poll, token: Token) -> io::Result<bool> {
+        loop {
+            let mut cx = Context::from_waker(&self.waker);
+            match self.incoming.poll_ready(&mut cx) {
+                task::Poll::Ready(Ok(())) => {}
+                task::Poll::Ready(Err(_disconnected)) => {
+                    trace!("incoming dropped");
+                    return Ok(false);
+                }
+                task::Poll::Pending => {
+                    if !self.paused {
+                        trace!("incoming full, pausing listener");
+                        poll.reregister(&self.listener, token, EventKind::error())?;
+                        self.paused = true;
+                    }
+                    return Ok(true);
+                }
+            }
+            if self.paused {
+                trace!("incoming has room, resuming listener");
+                poll.reregister(&self.listener, token,
+                                EventKind::readable() | EventKind::error())?;
+                self.paused = false;
+            }
+
+            let stream = match self.listener.accept() {
+                Ok((stream, peer_addr)) => {
+                    trace!("connection established from {}", peer_addr);
+                    self.options.accept(stream)?
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    return Ok(true);
+                }
+                Err(e) => return Err(e),
+            };
+            if self.incoming.start_send(Ok(stream)).is_err() {
+                trace!("incoming dropped");
+                return Ok(false);
             }
         }
     }
 }
 
-fn run(_tx: Sender<Message>, rx: Receiver<Message>) {
-    let poll = Poll::new().expect("srt poll creation error");
-    let mut events = Events::with_capacity(1000); // XXX
-    let mut tasks = Slab::with_capacity(1000); // XXX
-    let mut rx_done = false;
+fn run(
+    tx: &Sender<Message>,
+    rx: &Receiver<Message>,
+    load: &AtomicUsize,
+    readiness: &Readiness,
+) -> io::Result<()> {
+    let mut tasks = Slab::with_capacity(INITIAL_TASKS);
+
+    let res = run_loop(tx, rx, load, readiness, &mut tasks);
+    readiness.stop();
+
+    // whatever is still pending would otherwise wait forever
+    let reason = |res: &io::Result<()>| match *res {
+        Ok(()) => stopped(),
+        Err(ref e) => io::Error::new(e.kind(), e.to_string()),
+    };
+    for task in tasks.drain() {
+        task.fail(reason(&res));
+    }
+    while let Some(msg) = rx.recv() {
+        msg.fail(reason(&res));
+    }
+    res
+}
+
+fn run_loop(
+    tx: &Sender<Message>,
+    rx: &Receiver<Message>,
+    load: &AtomicUsize,
+    readiness: &Readiness,
+    tasks: &mut Slab<Task>,
+) -> io::Result<()> {
+    // the tasks share the poll with the sockets polled for readiness
+    let poll = &readiness.poll;
+    let mut events = Events::with_capacity(INITIAL_TASKS);
+    let mut state = State::Running;
+    let mut pending = Pending::default();
 
     let msg_index = tasks.insert(Task::Message());
     poll.register(&rx.inner.rx, Token(msg_index),
-                  EventKind::readable() | EventKind::error()).unwrap(); // XXX
+                  EventKind::readable() | EventKind::error())?;
 
     loop {
         trace!("turn of the loop");
-        if rx_done && tasks.len() == 0 {
-            break
+        // all but the message task
+        load.store(tasks.len() - 1, Ordering::SeqCst);
+        match state {
+            State::Shutdown => return Ok(()),
+            State::Done if tasks.len() == 1 => return Ok(()),
+            _ => {}
         }
 
         events.clear();
         // Wait for events
-        poll.poll(&mut events, Some(Duration::from_millis(1000))) // XXX
-            .expect("srt poll error");
+        poll.poll(&mut events, Some(LOOP_TIMEOUT))?;
         for event in &events {
-            match event.token() {
-                Token(index) => {
-                    let kind = event.kind();
-
-                    let task = tasks.get_mut(index).unwrap(); // XXX
-                    match task {
-                        Task::Message() => {
-                            // Do nothing
-                            assert_eq!(index, msg_index);
-                            if kind.is_readable() {
-                                trace!("got a message");
-                                enqueue(&rx, &poll, &mut tasks, &mut rx_done);
-                            } else if kind.is_error() {
-                                trace!("got an error");
-                                poll.deregister(&rx.inner.rx).unwrap();
-                            }
-                        }
-                        Task::Connecting(stream, _complete) => {
-                            poll.deregister(stream).unwrap();
-                            let t = tasks.remove(index);
-                            match t {
-                                Task::Connecting(stream, complete) => {
-                                    drop(complete.send(stream));
-                                    trace!("connection complete");
-                                }
-                                _ => unreachable!()
-                            }
-                        }
-                        Task::Listening(ref listener, ref mut incoming) => {
-                            accept(listener, incoming);
-                        }
-                    }
+            let Token(index) = event.token();
+            let kind = event.kind();
+
+            if index & READINESS_TOKEN != 0 {
+                readiness.handle(index & !READINESS_TOKEN, kind);
+            } else if index == msg_index {
+                if kind.is_error() {
+                    return Err(io::Error::new(io::ErrorKind::BrokenPipe,
+                                              "srt event loop channel broken"));
+                }
+                trace!("got a message");
+                enqueue(tx, rx, poll, tasks, &mut state, &mut pending)?;
+            } else {
+                handle(poll, tasks, index, kind);
+            }
+        }
+
+        for index in pending.resumed.drain(..) {
+            match tasks.get(index) {
+                Some(Task::Listening(ref listening)) if listening.paused => {
+                    handle(poll, tasks, index, EventKind::readable());
                 }
+                // stale wake up of a finished or reused task
+                _ => {}
+            }
+        }
+        if pending.canceled {
+            pending.canceled = false;
+            sweep(poll, tasks);
+        }
+        readiness.sweep();
+    }
+}
+
+/// Removes the tasks nobody waits for anymore, closing their sockets.
+fn sweep(poll: &Poll, tasks: &mut Slab<Task>) {
+    let canceled = tasks
+        .iter()
+        .filter(|&(_, task)| match *task {
+            Task::Message() => false,
+            Task::Connecting(_, _, ref complete) => complete.is_canceled(),
+            Task::Listening(ref listening) => listening.incoming.is_closed(),
+        })
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    for index in canceled {
+        match tasks.remove(index) {
+            Task::Connecting(stream, _addr, _complete) => {
+                trace!("connect canceled");
+                deregister(poll, &stream);
             }
+            Task::Listening(listening) => {
+                trace!("incoming dropped");
+                deregister(poll, &listening.listener);
+            }
+            Task::Message() => unreachable!(),
         }
     }
 }
 
+/// Deregisters the socket of a finished task. A failure only concerns
+/// that socket, which is closed anyway, so it doesn't stop the loop.
+fn deregister<S: AsSocket>(poll: &Poll, socket: &S) {
+    if let Err(e) = poll.deregister(socket) {
+        warn!("cannot deregister socket {}: {}", socket.as_socket().as_raw(), e);
+    }
+}
+
+/// Handles an event of the task at `index`, removing it once completed.
+///
+/// Failures are delivered to the task's future, the other tasks go on.
+fn handle(
+    poll: &Poll,
+    tasks: &mut Slab<Task>,
+    index: usize,
+    kind: EventKind,
+) {
+    let done = match tasks.get_mut(index) {
+        Some(Task::Connecting(..)) => true,
+        Some(Task::Listening(ref mut listening)) => {
+            if kind.is_error() {
+                let err = match listening.listener.state() {
+                    net::SocketState::Broken => {
+                        io::Error::new(io::ErrorKind::BrokenPipe, "listener broken")
+                    }
+                    state => io::Error::new(io::ErrorKind::NotConnected,
+                                            format!("listener {:?}", state)),
+                };
+                drop(listening.incoming.try_send(Err(err)));
+                true
+            } else {
+                !listening.accept(poll, Token(index))
+            }
+        }
+        // removed earlier during this turn
+        Some(Task::Message()) | None => false,
+    };
+    if !done {
+        return;
+    }
+
+    match tasks.remove(index) {
+        Task::Connecting(stream, addr, complete) => {
+            deregister(poll, &stream);
+            let res = connected(&stream);
+            stream.as_socket().connect_complete(&addr, &res);
+            let res = res.map(|()| stream);
+            trace!("connection complete: {:?}", res.as_ref().map(|_| ()));
+            drop(complete.send(res));
+        }
+        Task::Listening(listening) => {
+            deregister(poll, &listening.listener);
+        }
+        Task::Message() => unreachable!(),
+    }
+}
+
 impl<T> Sender<T> {
-    fn send(&self, t: T) {
-        self.tx.send(t).unwrap();
+    /// Queues a message for the event loop, or gives it back if the loop
+    /// is gone.
+    fn send(&self, t: T) -> Result<(), T> {
+        self.tx.send(t).map_err(|e| e.0)?;
         self.inner.notify();
+        Ok(())
     }
 }
 
@@ -324,13 +1106,13 @@ impl<T> Receiver<T> {
         self.rx.try_recv().ok()
     }
 
-    /// Returns whether there are messages to look at
-    fn drain(&self) {
+    /// Consumes the pending notifications
+    fn drain(&self) -> io::Result<()> {
         loop {
             match (&self.inner.rx).read(&mut [0; MSG_PLSIZE]) {
                 Ok(_) => {}
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
-                Err(e) => panic!("I/O error: {}", e),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
             }
         }
     }
@@ -346,8 +1128,10 @@ impl Channel {
 // Connecting
 ////////////////////////////////////////////////////////////////////////////////
 
+/// A connection in progress; dropping it aborts the connect.
 pub struct Connecting {
-    inner: channel::oneshot::Receiver<Stream>,
+    inner: channel::oneshot::Receiver<io::Result<Stream>>,
+    tx: Sender<Message>,
 }
 
 impl Future for Connecting {
@@ -358,26 +1142,37 @@ impl Future for Connecting {
         cx: &mut Context<'_>
     ) -> task::Poll<Self::Output> {
         match Pin::new(&mut self.inner).poll(cx) {
-            task::Poll::Ready(Ok(res)) => task::Poll::Ready(Ok(res)),
-            task::Poll::Ready(Err(_e)) => { // XXX
-                task::Poll::Ready(Err(io::Error::new(io::ErrorKind::Other,
-                                                     "canceled")))
-            }
+            task::Poll::Ready(Ok(res)) => task::Poll::Ready(res),
+            task::Poll::Ready(Err(_canceled)) => task::Poll::Ready(Err(stopped())),
             task::Poll::Pending => task::Poll::Pending,
         }
     }
 }
 
+impl Drop for Connecting {
+    fn drop(&mut self) {
+        self.inner.close();
+        // still pending, the socket is closed by the event loop
+        if let Ok(None) = self.inner.try_recv() {
+            drop(self.tx.send(Message::Canceled));
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Incoming
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Connections accepted by a listener; an error ends the stream.
+///
+/// Dropping it closes the listener.
 pub struct Incoming {
-    inner: channel::mpsc::Receiver<Stream>,
+    inner: channel::mpsc::Receiver<io::Result<Stream>>,
+    tx: Sender<Message>,
 }
 
 impl stream::Stream for Incoming {
-    type Item = Stream;
+    type Item = io::Result<Stream>;
 
     fn poll_next(
         mut self: Pin<&mut Self>,
@@ -386,3 +1181,355 @@ impl stream::Stream for Incoming {
         Pin::new(&mut self.inner).poll_next(cx)
     }
 }
+
+impl Drop for Incoming {
+    fn drop(&mut self) {
+        self.inner.close();
+        drop(self.tx.send(Message::Canceled));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Framed
+////////////////////////////////////////////////////////////////////////////////
+
+/// Number of messages the receive buffer of a `Framed` is sized for
+const POOL_MESSAGES: usize = 32;
+
+/// Adapts a live mode SRT stream to a `Sink` of messages and a `Stream` of
+/// received messages with their metadata.
+///
+/// Received messages are split off a shared buffer sized for several
+/// messages, which is reused once the `Bytes` handed out are dropped, so
+/// there is no allocation per message. The stream ends once the connection
+/// is lost or closed.
+pub struct Framed {
+    stream: Stream,
+    handle: Handle,
+    max_message_size: usize,
+    pool: BytesMut,
+    pending: Option<Bytes>,
+}
+
+impl Framed {
+    /// Wraps a message mode stream, making it nonblocking; the
+    /// process-wide reactor waits for it to become ready.
+    pub fn new(stream: Stream) -> io::Result<Framed> {
+        Framed::with_handle(stream, Handle::global()?)
+    }
+
+    /// Wraps a message mode stream, making it nonblocking; the given
+    /// reactor waits for it to become ready.
+    ///
+    /// Fails with `InvalidInput` unless the stream is in live mode: file
+    /// mode messages may be as large as the sending buffer, far too large
+    /// to pool.
+    pub fn with_handle(stream: Stream, handle: Handle) -> io::Result<Framed> {
+        let max_message_size = match stream.max_message_size()? {
+            Some(size) if stream.congestion()? == Congestion::Live => size,
+            _ => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "stream is not in live mode"));
+            }
+        };
+        stream.as_socket().set_send_nonblocking(true)?;
+        stream.as_socket().set_recv_nonblocking(true)?;
+
+        Ok(Framed {
+            stream: stream,
+            handle: handle,
+            max_message_size: max_message_size,
+            pool: BytesMut::new(),
+            pending: None,
+        })
+    }
+
+    pub fn get_ref(&self) -> &Stream {
+        &self.stream
+    }
+
+    /// Returns the stream, dropping a message not flushed yet.
+    pub fn into_inner(self) -> Stream {
+        self.stream
+    }
+
+    /// Returns the largest message the sink accepts.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+}
+
+impl stream::Stream for Framed {
+    type Item = io::Result<(Bytes, MsgInfo)>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>
+    ) -> task::Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            // still sized after a receive that would block
+            if this.pool.len() < this.max_message_size {
+                if this.pool.capacity() < this.max_message_size {
+                    // reclaims the buffer if all messages split off are dropped
+                    this.pool.reserve(this.max_message_size * POOL_MESSAGES);
+                }
+                this.pool.resize(this.max_message_size, 0);
+            }
+
+            match this.stream.as_socket().recv_msg(&mut this.pool) {
+                Ok((len, info)) => {
+                    this.pool.truncate(len);
+                    let msg = this.pool.split().freeze();
+                    return task::Poll::Ready(Some(Ok((msg, info))));
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(ref e) if is_disconnect(e) => return task::Poll::Ready(None),
+                Err(e) => return task::Poll::Ready(Some(Err(e))),
+            }
+
+            match this.handle.poll_ready(this.stream.as_socket(), EventKind::readable(), cx) {
+                task::Poll::Ready(Ok(())) => {}
+                task::Poll::Ready(Err(e)) => return task::Poll::Ready(Some(Err(e))),
+                task::Poll::Pending => return task::Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Returns whether a receive failed because the connection is gone, which
+/// ends a `Framed` instead of failing it over and over.
+fn is_disconnect(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::BrokenPipe
+        | io::ErrorKind::NotConnected
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted => true,
+        _ => false,
+    }
+}
+
+impl Sink<Bytes> for Framed {
+    type Error = io::Error;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>
+    ) -> task::Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, msg: Bytes) -> io::Result<()> {
+        if msg.len() > self.max_message_size {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "message larger than the maximum message size"));
+        }
+        self.pending = Some(msg);
+        Ok(())
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>
+    ) -> task::Poll<io::Result<()>> {
+        let this = &mut *self;
+        while let Some(ref msg) = this.pending {
+            match (&this.stream).write(msg) {
+                Ok(_) => this.pending = None,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    match this.handle.poll_ready(this.stream.as_socket(),
+                                                 EventKind::writable(), cx) {
+                        task::Poll::Ready(Ok(())) => {}
+                        res => return res,
+                    }
+                }
+                Err(e) => return task::Poll::Ready(Err(e)),
+            }
+        }
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>
+    ) -> task::Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Server
+////////////////////////////////////////////////////////////////////////////////
+
+/// Runs a handler for each connection of an `Incoming`, the async
+/// counterpart of `net::Server`.
+///
+/// Handlers run concurrently within the future returned by `serve`, so no
+/// particular runtime is needed to spawn them. Connections beyond
+/// `max_connections` wait in the `Incoming` (see
+/// `Builder::incoming_capacity`), then in the listener's backlog, until a
+/// handler completes.
+pub struct Server {
+    max_connections: usize,
+    on_error: Arc<dyn Fn(SocketAddr, io::Error) + Send + Sync>,
+    handle: ServerHandle,
+}
+
+/// Stops a running `Server`.
+#[derive(Clone)]
+pub struct ServerHandle {
+    inner: Arc<ServerState>,
+}
+
+struct ServerState {
+    shutdown: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server {
+            max_connections: 1024,
+            on_error: Arc::new(|addr, err| warn!("connection from {} failed: {}", addr, err)),
+            handle: ServerHandle {
+                inner: Arc::new(ServerState {
+                    shutdown: AtomicBool::new(false),
+                    waker: AtomicWaker::new(),
+                }),
+            },
+        }
+    }
+
+    /// Maximum number of connections handled at once (1024 by default)
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = cmp::max(max_connections, 1);
+        self
+    }
+
+    /// Called with the peer address whenever a handler fails (errors are
+    /// logged by default)
+    pub fn on_error<F>(mut self, on_error: F) -> Self
+    where
+        F: Fn(SocketAddr, io::Error) + Send + Sync + 'static,
+    {
+        self.on_error = Arc::new(on_error);
+        self
+    }
+
+    pub fn handle(&self) -> ServerHandle {
+        self.handle.clone()
+    }
+
+    /// Serves connections until shut down through a `ServerHandle`, then
+    /// waits for the running handlers to complete.
+    ///
+    /// Shutting down drops the `Incoming` at once, closing the listener
+    /// and the connections it had accepted but not handed out yet. Fails
+    /// if accepting from the listener fails.
+    pub async fn serve<F, Fut>(self, incoming: Incoming, mut handler: F) -> io::Result<()>
+    where
+        F: FnMut(Stream) -> Fut,
+        Fut: Future<Output = io::Result<()>>,
+    {
+        let failed = sync::Mutex::new(None);
+        let accepting = Accepting {
+            incoming: Some(incoming),
+            shutdown: Shutdown { inner: self.handle.inner.clone() },
+        };
+        let on_error = &self.on_error;
+
+        accepting
+            .for_each_concurrent(self.max_connections, |res| {
+                let conn = res.map(|stream| {
+                    let addr = stream
+                        .peer_addr()
+                        .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0)));
+                    (addr, handler(stream))
+                });
+                let failed = &failed;
+                async move {
+                    match conn {
+                        Ok((addr, handler)) => {
+                            if let Err(e) = handler.await {
+                                on_error(addr, e);
+                            }
+                        }
+                        // the Incoming ends after an error
+                        Err(e) => {
+                            *failed.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+                        }
+                    }
+                }
+            })
+            .await;
+
+        match failed.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for Server {
+    fn default() -> Server {
+        Server::new()
+    }
+}
+
+impl ServerHandle {
+    /// Stops accepting connections, closing the listener and the
+    /// connections not handed to a handler yet; `Server::serve` completes
+    /// once the running handlers are done.
+    pub fn shutdown(&self) {
+        self.inner.shutdown.store(true, Ordering::SeqCst);
+        self.inner.waker.wake();
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.inner.shutdown.load(Ordering::SeqCst)
+    }
+}
+
+/// The connections of a serving `Server`, ending once it is shut down.
+///
+/// Unlike `take_until` it drops the `Incoming` right away, rather than
+/// when the handlers still running are done.
+struct Accepting {
+    incoming: Option<Incoming>,
+    shutdown: Shutdown,
+}
+
+impl stream::Stream for Accepting {
+    type Item = io::Result<Stream>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>
+    ) -> task::Poll<Option<Self::Item>> {
+        if let task::Poll::Ready(()) = Pin::new(&mut self.shutdown).poll(cx) {
+            self.incoming = None;
+        }
+        match self.incoming {
+            Some(ref mut incoming) => Pin::new(incoming).poll_next(cx),
+            None => task::Poll::Ready(None),
+        }
+    }
+}
+
+/// Completes once the server is shut down.
+struct Shutdown {
+    inner: Arc<ServerState>,
+}
+
+impl Future for Shutdown {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<()> {
+        self.inner.waker.register(cx.waker());
+        if self.inner.shutdown.load(Ordering::SeqCst) {
+            task::Poll::Ready(())
+        } else {
+            task::Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "bonding")]
+
+use std::{
+    io::{Read, Write},
+    str,
+    time::Duration,
+    thread,
+};
+use libsrt_rs::net::{
+    Builder,
+    Bind,
+    Endpoint,
+    GROUPTYPE,
+    MEMBERSTATUS,
+};
+
+static MESSAGE: &str = "hello srt-group";
+
+#[test]
+fn group_broadcast_echo() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let server = Builder::new().group_connect(true).bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let server_thread = thread::spawn(move || {
+        let (peer, _peer_addr) = server.accept().unwrap();
+        let mut group = peer.into_group().unwrap();
+
+        let mut buf = [0; 2048];
+        let nread = group.read(&mut buf).unwrap();
+        assert_eq!(MESSAGE, str::from_utf8(&buf[0..nread]).unwrap());
+
+        group.write_all(&buf[0..nread]).unwrap();
+        thread::sleep(Duration::from_millis(500)); // XXX
+    });
+
+    let endpoints = [Endpoint::new(addr), Endpoint::new(addr)];
+    let mut client = Builder::new()
+        .connect_group(GROUPTYPE::SRT_GTYPE_BROADCAST, &endpoints)
+        .unwrap();
+
+    let members = client.members().unwrap();
+    assert_eq!(members.len(), 2);
+    assert!(members.iter().all(|m| m.peer_addr() == Some(addr)));
+    assert!(members.iter().all(|m| m.status() != MEMBERSTATUS::SRT_GST_BROKEN));
+
+    client.write_all(MESSAGE.as_bytes()).unwrap();
+
+    let mut buf = [0; 2048];
+    let nread = client.read(&mut buf).unwrap();
+    assert_eq!(MESSAGE, str::from_utf8(&buf[0..nread]).unwrap());
+
+    server_thread.join().unwrap();
+}
@@ -1,13 +1,35 @@
 use std::{
+    future,
     io::{self, Read, Write},
+    task,
+    net::{SocketAddr, UdpSocket},
     str,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+        Mutex,
+    },
     time::Duration,
     thread,
 };
+use async_std::task::block_on;
 use libsrt_rs::net::{
+    AsSocket,
     Builder,
     Bind,
-    Poll, Token, Events, EventKind,
+    Connect,
+    Congestion,
+    Dscp,
+    RejectReason,
+    SocketState, StateWatcher,
+    Fec, FecArq, FecLayout,
+    TRANSTYPE,
+    Version,
+    Poll, Token, Events, EventKind, Watermark,
+    ShardedPoll, ShardedReactor,
+    Server,
+    Observer,
+    int,
 };
 
 static MESSAGE: &str = "hello srt-net";
@@ -192,3 +214,766 @@ fn net_async_echo() {
 
     server_thread.join().unwrap();
 }
+
+#[test]
+fn net_connect_local_addr() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let server = Builder::new().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let server_thread = thread::spawn(move || {
+        let mut peers = Vec::new();
+        for _ in 0..2 {
+            let (peer, peer_addr) = server.accept().unwrap();
+            peers.push((peer, peer_addr));
+        }
+        thread::sleep(Duration::from_millis(500)); // XXX
+        peers.into_iter().map(|(_, addr)| addr).collect::<Vec<_>>()
+    });
+
+    // fixed source interface, the port is picked when binding
+    let client = Builder::new()
+        .local_addr(try_addr)
+        .reuse_addr(true)
+        .connect(&addr)
+        .unwrap();
+    assert!(client.reuse_addr().unwrap());
+    let local_addr = client.local_addr().unwrap();
+    assert_eq!(local_addr.ip(), try_addr.ip());
+    assert_ne!(local_addr.port(), 0);
+    assert_eq!(client.peer_addr().unwrap(), addr);
+
+    // adopted UDP socket
+    let udp = UdpSocket::bind(&try_addr).unwrap();
+    let udp_addr = udp.local_addr().unwrap();
+    let adopted = Builder::new().connect_from(udp, &addr).unwrap();
+    assert_eq!(adopted.local_addr().unwrap(), udp_addr);
+
+    let peer_addrs = server_thread.join().unwrap();
+    assert!(peer_addrs.contains(&local_addr));
+    assert!(peer_addrs.contains(&udp_addr));
+}
+
+#[test]
+fn net_peer_version() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let server = Builder::new().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let server_thread = thread::spawn(move || {
+        let (peer, _peer_addr) = server.accept().unwrap();
+        assert_eq!(peer.peer_version().unwrap(), libsrt_sys::version());
+    });
+
+    let client = Builder::new().connect(&addr).unwrap();
+    assert_eq!(client.peer_version().unwrap(), libsrt_sys::version());
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn net_min_peer_version() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let server = Builder::new()
+        .min_peer_version(Version::new(255, 0, 0))
+        .bind(&try_addr)
+        .unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let err = Builder::new().connect(&addr).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    assert_eq!(RejectReason::from_error(&err), Some(RejectReason::Version));
+
+    server.close().unwrap();
+}
+
+#[test]
+fn net_fec() {
+    let fec: Fec = "fec,cols:10,rows:5,layout:staircase,arq:onreq".parse().unwrap();
+    assert_eq!(fec, Fec::new(10).rows(5)
+               .layout(FecLayout::Staircase).arq(FecArq::OnReq));
+    assert_eq!(fec.to_string(), "fec,cols:10,rows:5,layout:staircase,arq:onreq");
+    assert!("fec,rows:5".parse::<Fec>().is_err());
+    assert!("fec,cols:10,layout:diagonal".parse::<Fec>().is_err());
+    assert!("rs,cols:10".parse::<Fec>().is_err());
+    // a typo is not taken for a key of a newer libsrt
+    assert!("fec,cols:10,layuot:staircase".parse::<Fec>().is_err());
+
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let server = Builder::new().fec(fec).bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let server_thread = thread::spawn(move || {
+        let (peer, _peer_addr) = server.accept().unwrap();
+        let negotiated = peer.fec().unwrap().unwrap();
+        assert_eq!(negotiated.cols, 10);
+        assert_eq!(negotiated.rows, 5);
+    });
+
+    // the caller only asks for FEC, the rest is negotiated
+    let client = Builder::new().fec(Fec::new(10).rows(5)).connect(&addr).unwrap();
+    let negotiated = client.fec().unwrap().unwrap();
+    assert_eq!(negotiated.layout, Some(FecLayout::Staircase));
+    assert_eq!(negotiated.arq, Some(FecArq::OnReq));
+
+    server_thread.join().unwrap();
+
+    assert!(Builder::new().fec(Fec::new(0)).connect(&addr).is_err());
+}
+
+#[test]
+fn net_congestion() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    // explicit congestion wins over the preset, whatever the call order
+    let builder = || Builder::new()
+        .congestion(Congestion::File)
+        .trans_type(TRANSTYPE::SRTT_LIVE);
+
+    let server = builder().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let server_thread = thread::spawn(move || {
+        let (peer, _peer_addr) = server.accept().unwrap();
+        assert_eq!(peer.congestion().unwrap(), Congestion::File);
+    });
+
+    let client = builder().connect(&addr).unwrap();
+    assert_eq!(client.congestion().unwrap(), Congestion::File);
+
+    server_thread.join().unwrap();
+
+    let server = Builder::new()
+        .trans_type(TRANSTYPE::SRTT_FILE)
+        .bind(&try_addr)
+        .unwrap();
+    assert_eq!(server.as_socket().congestion().unwrap(), "file");
+}
+
+#[test]
+fn net_message_api() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let builder = || Builder::new()
+        .trans_type(TRANSTYPE::SRTT_FILE)
+        .message_api(true);
+
+    let server = builder().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let server_thread = thread::spawn(move || {
+        let (peer, _peer_addr) = server.accept().unwrap();
+        let mut peer = peer.into_message_stream().unwrap();
+        assert_eq!(peer.max_message_size(), None);
+
+        // until the size is agreed on, a buffer has to hold whatever the
+        // receiving buffer can
+        let mut buf = [0; 8192];
+        assert!(peer.recv_message_size() > buf.len());
+        assert_eq!(peer.read(&mut buf).unwrap_err().kind(),
+                   io::ErrorKind::InvalidInput);
+        let mut large = vec![0; peer.recv_message_size()];
+
+        // messages arrive one by one, with their boundaries
+        assert_eq!(peer.read(&mut large).unwrap(), 3000);
+        peer.set_max_message_size(Some(4096));
+        assert_eq!(peer.recv_message_size(), 4096);
+        assert_eq!(peer.read(&mut buf).unwrap(), 10);
+
+        assert_eq!(peer.read(&mut buf[..100]).unwrap_err().kind(),
+                   io::ErrorKind::InvalidInput);
+        thread::sleep(Duration::from_millis(500)); // XXX
+    });
+
+    let client = builder().connect(&addr).unwrap();
+    let mut client = client.into_message_stream().unwrap();
+    client.write_all(&[1; 3000]).unwrap();
+    client.write_all(&[2; 10]).unwrap();
+
+    server_thread.join().unwrap();
+
+    let client = client.into_stream();
+    assert!(client.into_byte_stream().is_err());
+
+    // live mode: messages are bounded by the payload size
+    let server = Builder::new().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+    let server_thread = thread::spawn(move || {
+        server.accept().unwrap();
+    });
+    let live = Builder::new().connect(&addr).unwrap();
+    let mut live = live.into_message_stream().unwrap();
+    assert_eq!(live.max_message_size(), Some(1316));
+    assert_eq!(live.read(&mut [0; 100]).unwrap_err().kind(),
+               io::ErrorKind::InvalidInput);
+    assert_eq!(live.write(&[0; 2000]).unwrap_err().kind(),
+               io::ErrorKind::InvalidInput);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn net_close_drain() {
+    const LEN: usize = 1024 * 1024;
+
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let builder = || Builder::new()
+        .trans_type(TRANSTYPE::SRTT_FILE)
+        .message_api(false)
+        .linger(None);
+
+    let server = builder().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let server_thread = thread::spawn(move || {
+        let (mut peer, _peer_addr) = server.accept().unwrap();
+        server.close().unwrap();
+
+        let mut buf = [0; 8192];
+        let mut nread = 0;
+        while nread < LEN {
+            match peer.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => nread += len,
+                Err(_) => break,
+            }
+        }
+        nread
+    });
+
+    let mut client = builder().connect(&addr).unwrap();
+    assert_eq!(client.as_socket().linger().unwrap(), None);
+    client.write_all(&vec![7; LEN]).unwrap();
+    client.close_timeout(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(server_thread.join().unwrap(), LEN);
+
+    // linger is kept in whole seconds, rounded up
+    let server = builder().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = builder().linger(Some(Duration::from_millis(1500))).connect(&addr).unwrap();
+    assert_eq!(client.as_socket().linger().unwrap(), Some(Duration::from_secs(2)));
+    drop(server);
+}
+
+#[test]
+fn net_state_watcher() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let server = Builder::new().bind(&try_addr).unwrap();
+    assert_eq!(server.state(), SocketState::Listening);
+    let addr = server.local_addr().unwrap();
+
+    let server_thread = thread::spawn(move || {
+        let (peer, _peer_addr) = server.accept().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        drop(peer);
+    });
+
+    let client = Builder::new().connect(&addr).unwrap();
+
+    const CLIENT_TOKEN: Token = Token(0);
+    let mut watcher = StateWatcher::new().unwrap();
+    assert_eq!(watcher.watch(&client, CLIENT_TOKEN).unwrap(),
+               SocketState::Connected);
+
+    let mut changes = Vec::new();
+    while changes.is_empty() {
+        changes = watcher.poll(Some(Duration::from_millis(5000))).unwrap();
+    }
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].token(), CLIENT_TOKEN);
+    assert_eq!(changes[0].from(), SocketState::Connected);
+    assert!(changes[0].to().is_terminal());
+    assert!(client.state().is_terminal());
+    assert!(watcher.is_empty());
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn net_read_timeout() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let server = Builder::new().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let server_thread = thread::spawn(move || {
+        let (_peer, _peer_addr) = server.accept().unwrap();
+        thread::sleep(Duration::from_millis(500)); // XXX
+    });
+
+    let mut client = Builder::new().connect(&addr).unwrap();
+    assert_eq!(client.read_timeout().unwrap(), None);
+    assert_eq!(client.set_read_timeout(Some(Duration::from_secs(0)))
+               .unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+    client.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+    client.set_write_timeout(Some(Duration::from_millis(100))).unwrap();
+    assert_eq!(client.read_timeout().unwrap(), Some(Duration::from_millis(100)));
+    assert_eq!(client.write_timeout().unwrap(), Some(Duration::from_millis(100)));
+
+    let mut buf = [0; 2048];
+    assert_eq!(client.read(&mut buf).unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn net_max_message_size() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let server = Builder::new().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+    let server_thread = thread::spawn(move || {
+        server.accept().unwrap();
+    });
+
+    let live = Builder::new().connect(&addr).unwrap();
+    assert_eq!(live.mss().unwrap(), 1500);
+    assert_eq!(live.payload_size().unwrap(), 1316);
+    assert_eq!(live.max_message_size().unwrap(), Some(1316));
+
+    let buf = [0; 3000];
+    let chunks = live.split_messages(&buf).unwrap().map(|c| c.len()).collect::<Vec<_>>();
+    assert_eq!(chunks, vec![1316, 1316, 368]);
+
+    server_thread.join().unwrap();
+
+    let builder = |message_api| Builder::new()
+        .trans_type(TRANSTYPE::SRTT_FILE)
+        .message_api(message_api);
+
+    for &message_api in &[true, false] {
+        let server = builder(message_api).bind(&try_addr).unwrap();
+        let addr = server.local_addr().unwrap();
+        let server_thread = thread::spawn(move || {
+            server.accept().unwrap();
+        });
+
+        let file = builder(message_api).connect(&addr).unwrap();
+        match file.max_message_size().unwrap() {
+            Some(size) => assert!(message_api && size > 1456),
+            None => assert!(!message_api),
+        }
+
+        server_thread.join().unwrap();
+    }
+}
+
+#[test]
+fn net_send_watermark() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let server = Builder::new().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+    let server_thread = thread::spawn(move || {
+        let (peer, _peer_addr) = server.accept().unwrap();
+        thread::sleep(Duration::from_millis(500)); // XXX
+        drop(peer);
+    });
+
+    let mut client = Builder::new().nonblocking(true).connect(&addr).unwrap();
+    let level = client.send_buffer().unwrap();
+    assert_eq!(level.packets(), 0);
+    assert_eq!(level.bytes(), Some(0));
+    assert!(level.capacity() > 0);
+    assert_eq!(client.recv_buffer().unwrap().bytes(), None);
+
+    let poll = Poll::new().unwrap();
+    assert!(Watermark::new(1316, 0).is_err());
+    let watermark = Watermark::new(0, 1316).unwrap();
+    poll.register_watermark(&client, Token(0), EventKind::writable(), watermark)
+        .unwrap();
+
+    let mut events = Events::with_capacity(4);
+    poll.poll(&mut events, Some(Duration::from_secs(1))).unwrap();
+    assert!(events.iter().any(|e| e.kind().is_writable()));
+
+    // two packets are above the high watermark until acknowledged
+    let buf = [0; 1316];
+    client.write(&buf).unwrap();
+    client.write(&buf).unwrap();
+    assert!(client.send_buffer().unwrap().bytes().unwrap() > watermark.high());
+    events.clear();
+    poll.poll(&mut events, Some(Duration::from_millis(1))).unwrap();
+    assert!(!events.iter().any(|e| e.kind().is_writable()));
+
+    // ... and writable again once the peer acknowledged them
+    events.clear();
+    for _ in 0..100 {
+        poll.poll(&mut events, Some(Duration::from_millis(10))).unwrap();
+        if !events.is_empty() {
+            break;
+        }
+    }
+    assert!(events.iter().any(|e| e.kind().is_writable()));
+    assert!(client.send_buffer().unwrap().bytes().unwrap() <= watermark.low());
+
+    // a socket closed while throttled is reported, not failing the poll
+    client.write(&buf).unwrap();
+    client.write(&buf).unwrap();
+    events.clear();
+    poll.poll(&mut events, Some(Duration::from_millis(1))).unwrap();
+    drop(client);
+    events.clear();
+    poll.poll(&mut events, Some(Duration::from_millis(100))).unwrap();
+    assert!(events.iter().any(|e| e.token() == Token(0) && e.kind().is_error()));
+    events.clear();
+    poll.poll(&mut events, Some(Duration::from_millis(100))).unwrap();
+    assert!(events.is_empty());
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn net_ip_marking() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let server = Builder::new().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+    let server_thread = thread::spawn(move || {
+        server.accept().unwrap();
+    });
+
+    let client = Builder::new()
+        .ip_ttl(16)
+        .dscp(Dscp::AF41)
+        .connect(&addr)
+        .unwrap();
+    assert_eq!(client.ip_ttl().unwrap(), 16);
+    assert_eq!(client.ip_tos().unwrap(), 0x88);
+    assert_eq!(client.dscp().unwrap(), Some(Dscp::AF41));
+
+    server_thread.join().unwrap();
+
+    assert_eq!(Dscp::EF.to_tos(0x01), 0xb9);
+    assert_eq!(Dscp::from_tos(0xb8), Some(Dscp::EF));
+    assert_eq!(Dscp::from_codepoint(1), None);
+}
+
+#[test]
+fn net_sharded_poll() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let listeners = (0..4)
+        .map(|_| Builder::new().nonblocking(true).bind(&try_addr).unwrap())
+        .collect::<Vec<_>>();
+
+    let poll = ShardedPoll::new(2).unwrap();
+    for (i, listener) in listeners.iter().enumerate() {
+        poll.register(listener, Token(i), EventKind::readable()).unwrap();
+    }
+    assert_eq!((poll.load(0), poll.load(1)), (2, 2));
+    assert!(poll.register(&listeners[0], Token(9), EventKind::readable()).is_err());
+
+    // emptying one shard moves a socket over from the other one
+    let shard0 = listeners
+        .iter()
+        .filter(|listener| poll.shard_of(*listener) == Some(0))
+        .collect::<Vec<_>>();
+    for listener in shard0 {
+        poll.deregister(listener).unwrap();
+    }
+    assert_eq!((poll.load(0), poll.load(1)), (1, 1));
+
+    // the token follows the migrated socket
+    let (i, moved) = listeners
+        .iter()
+        .enumerate()
+        .find(|(_, listener)| poll.shard_of(*listener) == Some(0))
+        .unwrap();
+    let addr = moved.local_addr().unwrap();
+    let client_thread = thread::spawn(move || {
+        Builder::new().connect(&addr).unwrap();
+    });
+
+    let mut events = Events::with_capacity(4);
+    poll.poll(0, &mut events, Some(Duration::from_secs(1))).unwrap();
+    assert!(events.iter().any(|e| e.token() == Token(i) && e.kind().is_readable()));
+
+    client_thread.join().unwrap();
+}
+
+#[test]
+fn net_sharded_poll_migrate() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    // listeners with a connection waiting are readable all the time
+    let listeners = (0..4)
+        .map(|_| Builder::new().nonblocking(true).bind(&try_addr).unwrap())
+        .collect::<Vec<_>>();
+    let clients = listeners
+        .iter()
+        .map(|listener| Builder::new().connect(&listener.local_addr().unwrap()).unwrap())
+        .collect::<Vec<_>>();
+
+    let poll = Arc::new(ShardedPoll::new(2).unwrap());
+    for (i, listener) in listeners.iter().enumerate() {
+        poll.register(listener, Token(i), EventKind::readable()).unwrap();
+    }
+
+    // one thread per shard, as meant to be used
+    let stop = Arc::new(AtomicBool::new(false));
+    let pollers = (0..poll.shards())
+        .map(|shard| {
+            let poll = poll.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let mut events = Events::with_capacity(4);
+                while !stop.load(Ordering::SeqCst) {
+                    events.clear();
+                    poll.poll(shard, &mut events, Some(Duration::from_millis(1))).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // emptying shard 0 migrates a socket away from the polling shard 1
+    for _ in 0..50 {
+        let shard0 = listeners
+            .iter()
+            .enumerate()
+            .filter(|(_, listener)| poll.shard_of(*listener) == Some(0))
+            .collect::<Vec<_>>();
+        for &(_, listener) in &shard0 {
+            poll.deregister(listener).unwrap();
+        }
+        assert_eq!((poll.load(0), poll.load(1)), (1, 1));
+        for (i, listener) in shard0 {
+            poll.register(listener, Token(i), EventKind::readable()).unwrap();
+        }
+    }
+
+    stop.store(true, Ordering::SeqCst);
+    for poller in pollers {
+        poller.join().unwrap();
+    }
+    drop(clients);
+}
+
+#[test]
+fn net_sharded_reactor() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let seen = Arc::new(Mutex::new(vec![None; 4]));
+    let seen2 = seen.clone();
+    let reactor = ShardedReactor::new(2, move |shard, event| {
+        let Token(i) = event.token();
+        seen2.lock().unwrap()[i] = Some(shard);
+    }).unwrap();
+
+    // listeners with a connection waiting are readable all the time
+    let listeners = (0..4)
+        .map(|_| Builder::new().nonblocking(true).bind(&try_addr).unwrap())
+        .collect::<Vec<_>>();
+    let clients = listeners
+        .iter()
+        .map(|listener| Builder::new().connect(&listener.local_addr().unwrap()).unwrap())
+        .collect::<Vec<_>>();
+    for (i, listener) in listeners.iter().enumerate() {
+        reactor.poll().register(listener, Token(i), EventKind::readable()).unwrap();
+    }
+
+    // each shard's thread handles the events of its own sockets
+    for _ in 0..50 {
+        if seen.lock().unwrap().iter().all(Option::is_some) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    for (i, listener) in listeners.iter().enumerate() {
+        assert_eq!(seen.lock().unwrap()[i], reactor.poll().shard_of(listener));
+    }
+
+    reactor.shutdown().unwrap().unwrap();
+    drop(clients);
+}
+
+#[test]
+#[cfg(feature = "stream")]
+fn net_poll_ready() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+
+    let server = Builder::new().bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+    let server_thread = thread::spawn(move || {
+        let (mut peer, _peer_addr) = server.accept().unwrap();
+        thread::sleep(Duration::from_millis(200));
+        peer.write(MESSAGE.as_bytes()).unwrap();
+        thread::sleep(Duration::from_millis(500)); // XXX
+    });
+
+    let mut client = Builder::new().nonblocking(true).connect(&addr).unwrap();
+    block_on(future::poll_fn(|cx| client.poll_write_ready(cx))).unwrap();
+
+    let mut buf = [0; 2048];
+    let nread = block_on(future::poll_fn(|cx| loop {
+        match client.poll_read_ready(cx) {
+            task::Poll::Ready(Ok(())) => {}
+            task::Poll::Ready(Err(e)) => return task::Poll::Ready(Err(e)),
+            task::Poll::Pending => return task::Poll::Pending,
+        }
+        match (&client).read(&mut buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            res => return task::Poll::Ready(res),
+        }
+    })).unwrap();
+    assert_eq!(MESSAGE, str::from_utf8(&buf[0..nread]).unwrap());
+    client.write(&buf[0..nread]).unwrap();
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn net_server() {
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+    let listener = Builder::new().bind(&try_addr).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let errors2 = errors.clone();
+    let server = Server::new()
+        .threads(2)
+        .on_error(move |addr, err| errors2.lock().unwrap().push((addr, err.kind())));
+    let handle = server.handle();
+
+    let server_thread = thread::spawn(move || {
+        server.serve(listener, |mut stream, _addr| {
+            let mut buf = [0; 2048];
+            let nread = stream.read(&mut buf)?;
+            if &buf[0..nread] == b"fail" {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "fail"));
+            }
+            stream.write_all(&buf[0..nread])?;
+            thread::sleep(Duration::from_millis(200)); // XXX
+            Ok(())
+        })
+    });
+
+    let clients = (0..3)
+        .map(|_| {
+            thread::spawn(move || {
+                let mut client = Builder::new().connect(&addr).unwrap();
+                client.write_all(MESSAGE.as_bytes()).unwrap();
+                let mut buf = [0; 2048];
+                let nread = client.read(&mut buf).unwrap();
+                assert_eq!(MESSAGE, str::from_utf8(&buf[0..nread]).unwrap());
+                thread::sleep(Duration::from_millis(200)); // XXX
+            })
+        })
+        .collect::<Vec<_>>();
+    for client in clients {
+        client.join().unwrap();
+    }
+
+    let mut client = Builder::new().connect(&addr).unwrap();
+    client.write_all(b"fail").unwrap();
+    thread::sleep(Duration::from_millis(500)); // XXX
+
+    handle.shutdown();
+    server_thread.join().unwrap().unwrap();
+    assert_eq!(handle.active(), 0);
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].1, io::ErrorKind::InvalidData);
+}
+
+#[derive(Debug, PartialEq)]
+enum Record {
+    Connecting,
+    Connected(int),
+    ConnectFailed(io::ErrorKind),
+    Accepted(int),
+    Broken(int),
+    Closed(int),
+    Sent(int, usize),
+    Received(int, usize),
+}
+
+struct Recorder(Mutex<Vec<Record>>);
+
+impl Observer for Recorder {
+    fn connecting(&self, _sock: int, _peer: &SocketAddr) {
+        self.0.lock().unwrap().push(Record::Connecting);
+    }
+    fn connected(&self, sock: int, _peer: &SocketAddr) {
+        self.0.lock().unwrap().push(Record::Connected(sock));
+    }
+    fn connect_failed(&self, _sock: int, _peer: &SocketAddr, err: &io::Error) {
+        self.0.lock().unwrap().push(Record::ConnectFailed(err.kind()));
+    }
+    fn accepted(&self, _listener: int, sock: int, _peer: &SocketAddr) {
+        self.0.lock().unwrap().push(Record::Accepted(sock));
+    }
+    fn broken(&self, sock: int) {
+        self.0.lock().unwrap().push(Record::Broken(sock));
+    }
+    fn closed(&self, sock: int) {
+        self.0.lock().unwrap().push(Record::Closed(sock));
+    }
+    fn sent(&self, sock: int, bytes: usize) {
+        self.0.lock().unwrap().push(Record::Sent(sock, bytes));
+    }
+    fn received(&self, sock: int, bytes: usize) {
+        self.0.lock().unwrap().push(Record::Received(sock, bytes));
+    }
+}
+
+#[test]
+fn net_observer() {
+    let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+    let builder = Builder::new().observer(recorder.clone());
+
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+    let server = builder.bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let mut client = builder.connect(&addr).unwrap();
+    let client_id = client.as_socket().as_raw();
+    let (mut peer, _peer_addr) = server.accept().unwrap();
+    let peer_id = peer.as_socket().as_raw();
+
+    client.write_all(MESSAGE.as_bytes()).unwrap();
+    let mut buf = [0; 2048];
+    let nread = peer.read(&mut buf).unwrap();
+    assert_eq!(nread, MESSAGE.len());
+
+    let poll = Poll::new().unwrap();
+    poll.register(&peer, Token(0), EventKind::readable() | EventKind::error()).unwrap();
+    drop(client);
+
+    let mut events = Events::with_capacity(1);
+    for _ in 0..50 {
+        events.clear();
+        poll.poll(&mut events, Some(Duration::from_millis(100))).unwrap();
+        if events.iter().any(|event| event.kind().is_error()) {
+            break;
+        }
+    }
+    drop(peer);
+
+    assert_eq!(*recorder.0.lock().unwrap(), vec![
+        Record::Connecting,
+        Record::Connected(client_id),
+        Record::Accepted(peer_id),
+        Record::Sent(client_id, MESSAGE.len()),
+        Record::Received(peer_id, MESSAGE.len()),
+        Record::Closed(client_id),
+        Record::Broken(peer_id),
+        Record::Closed(peer_id),
+    ]);
+
+    // nobody is listening there
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = udp.local_addr().unwrap();
+    recorder.0.lock().unwrap().clear();
+    assert!(builder.clone().connect_timeout(Duration::from_millis(500)).connect(&addr).is_err());
+    let records = recorder.0.lock().unwrap();
+    assert_eq!(records[0], Record::Connecting);
+    assert_eq!(records[1], Record::ConnectFailed(io::ErrorKind::TimedOut));
+}
@@ -1,17 +1,34 @@
 use std::{
-    time::Duration,
+    io::{self, Write},
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
     thread,
 };
 use libsrt_rs::{
     net::{
         self,
         Bind,
+        Congestion,
+        Observer,
+        TRANSTYPE,
     },
     stream::{
         self,
+        Framed,
+        Handle,
+        Server,
     },
 };
-use futures::stream::StreamExt;
+use bytes::Bytes;
+use futures::{
+    future,
+    sink::SinkExt,
+    stream::StreamExt,
+};
 use async_std::task;
 
 #[test]
@@ -53,10 +70,324 @@ fn stream_echo_server() {
     });
 
     let accept_fnt = builder.listen(server).take(1).collect::<Vec<_>>();
-    let _res = task::block_on(accept_fnt);
+    let res = task::block_on(accept_fnt);
+    assert!(res[0].is_ok());
     drop(builder);  // XXX cannot access stderr during shutdown
 
     assert_eq!(1, 1);
 
     client_thread.join().unwrap();
 }
+
+#[test]
+fn stream_connect_error() {
+    let builder = stream::Builder::with_handle(Handle::new(1).unwrap())
+        .connect_timeout(Duration::from_millis(500));
+
+    // nobody is listening there
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = udp.local_addr().unwrap();
+
+    let start = Instant::now();
+    let err = task::block_on(builder.connect(addr)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() < Duration::from_secs(3));
+
+    // dropping a pending connect aborts it
+    drop(builder.connect(addr));
+    assert!(builder.is_running());
+
+    let clone = builder.clone();
+    builder.shutdown().unwrap().unwrap();
+    assert!(!clone.is_running());
+
+    let err = task::block_on(clone.connect(addr)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+}
+
+#[test]
+fn stream_incoming_backpressure() {
+    let builder = stream::Builder::with_handle(Handle::new(1).unwrap())
+        .backlog(8)
+        .incoming_capacity(1);
+
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+    let server = builder.bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let client_thread = thread::spawn(move || {
+        let clients = (0..4)
+            .map(|_| net::Builder::new().connect(&addr).unwrap())
+            .collect::<Vec<_>>();
+
+        thread::sleep(Duration::from_millis(1000)); // XXX
+        drop(clients);
+    });
+
+    // none of the connections is dropped while the consumer lags behind
+    let mut incoming = builder.listen(server);
+    for _ in 0..4 {
+        thread::sleep(Duration::from_millis(100));
+        let peer = task::block_on(incoming.next()).unwrap();
+        assert!(peer.is_ok());
+    }
+
+    client_thread.join().unwrap();
+    builder.shutdown().unwrap().unwrap();
+}
+
+#[test]
+fn stream_options() {
+    let options = net::Builder::new()
+        .trans_type(TRANSTYPE::SRTT_FILE)
+        .latency(Duration::from_millis(200))
+        .passphrase("stream options test");
+    let builder = stream::Builder::with_handle(Handle::new(1).unwrap())
+        .options(options.clone());
+
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+    let server = builder.bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let client_thread = thread::spawn(move || {
+        let client = options.connect(&addr).unwrap();
+        assert_eq!(client.congestion().unwrap(), Congestion::File);
+
+        thread::sleep(Duration::from_millis(500)); // XXX
+    });
+
+    let mut incoming = builder.listen(server);
+    let peer = task::block_on(incoming.next()).unwrap().unwrap();
+    assert_eq!(peer.congestion().unwrap(), Congestion::File);
+    assert_eq!(peer.latency().unwrap(), Duration::from_millis(200));
+
+    client_thread.join().unwrap();
+
+    // a wrong passphrase is rejected
+    let server = net::Builder::new()
+        .trans_type(TRANSTYPE::SRTT_FILE)
+        .passphrase("another passphrase")
+        .bind(&try_addr)
+        .unwrap();
+    let addr = server.local_addr().unwrap();
+    let err = task::block_on(builder.connect(addr)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+
+    builder.shutdown().unwrap().unwrap();
+}
+
+#[test]
+fn stream_incoming_drop() {
+    let builder = stream::Builder::with_handle(Handle::new(1).unwrap());
+
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+    let server = builder.bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+
+    drop(builder.listen(server));
+    thread::sleep(Duration::from_millis(100));
+
+    // the listener is closed along with the Incoming
+    let client = net::Builder::new()
+        .connect_timeout(Duration::from_millis(500))
+        .connect(&addr);
+    assert!(client.is_err());
+
+    builder.shutdown().unwrap().unwrap();
+}
+
+#[test]
+fn stream_reactor_handle() {
+    let global = stream::Builder::new().unwrap();
+    assert!(global.is_running());
+    assert!(Handle::global().unwrap().is_running());
+
+    let handle = Handle::new(2).unwrap();
+    assert_eq!(handle.threads(), 2);
+    assert!(Handle::new(0).is_err());
+
+    // listeners are spread over the threads of the reactor
+    let builder = stream::Builder::with_handle(handle.clone());
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+    let servers = (0..2)
+        .map(|_| builder.bind(&try_addr).unwrap())
+        .collect::<Vec<_>>();
+    let addrs = servers
+        .iter()
+        .map(|server| server.local_addr().unwrap())
+        .collect::<Vec<_>>();
+
+    let client_thread = thread::spawn(move || {
+        let clients = addrs
+            .iter()
+            .map(|addr| net::Builder::new().connect(addr).unwrap())
+            .collect::<Vec<_>>();
+
+        thread::sleep(Duration::from_millis(500)); // XXX
+        drop(clients);
+    });
+
+    for server in servers {
+        let mut incoming = builder.listen(server);
+        let peer = task::block_on(incoming.next()).unwrap();
+        assert!(peer.is_ok());
+    }
+
+    client_thread.join().unwrap();
+
+    // the Incomings are dropped, so the reactor stops without waiting
+    let start = Instant::now();
+    handle.shutdown_timeout(Duration::from_secs(5)).unwrap().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(!builder.is_running());
+}
+
+#[test]
+fn stream_framed() {
+    let builder = stream::Builder::new().unwrap();
+
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+    let server = builder.bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+    let mut incoming = builder.listen(server);
+
+    let client = task::block_on(builder.connect(addr)).unwrap();
+    let peer = task::block_on(incoming.next()).unwrap().unwrap();
+
+    let mut client = Framed::new(client).unwrap();
+    let mut peer = Framed::new(peer).unwrap();
+    assert_eq!(client.max_message_size(), 1316);
+    assert!(task::block_on(client.send(Bytes::from(vec![0; 1317]))).is_err());
+
+    task::block_on(async {
+        client.send(Bytes::from_static(b"first")).await.unwrap();
+        client.send(Bytes::from(vec![7; 1316])).await.unwrap();
+
+        let (msg, first) = peer.next().await.unwrap().unwrap();
+        assert_eq!(&msg[..], b"first");
+        let (msg, second) = peer.next().await.unwrap().unwrap();
+        assert_eq!(msg.len(), 1316);
+        assert_eq!(second.msgno(), first.msgno() + 1);
+
+        // a lost connection ends the stream
+        drop(client);
+        assert!(peer.next().await.is_none());
+    });
+
+    // file mode messages are too large to be pooled
+    let builder = builder.options(net::Builder::new()
+        .trans_type(TRANSTYPE::SRTT_FILE)
+        .message_api(true));
+    let server = builder.bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+    let mut incoming = builder.listen(server);
+
+    let client = task::block_on(builder.connect(addr)).unwrap();
+    let _peer = task::block_on(incoming.next()).unwrap().unwrap();
+    let err = Framed::new(client).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn stream_server() {
+    let builder = stream::Builder::new().unwrap();
+
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+    let listener = builder.bind(&try_addr).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = Server::new().max_connections(2);
+    let handle = server.handle();
+
+    let client_thread = thread::spawn(move || {
+        let clients = (0..3)
+            .map(|_| net::Builder::new().connect(&addr).unwrap())
+            .collect::<Vec<_>>();
+        for mut client in clients {
+            client.write_all(b"hello").unwrap();
+        }
+        thread::sleep(Duration::from_millis(500)); // XXX
+        handle.shutdown();
+    });
+
+    let served = AtomicUsize::new(0);
+    let running = AtomicUsize::new(0);
+    let max_running = AtomicUsize::new(0);
+    let res = task::block_on(server.serve(builder.listen(listener), |stream| {
+        let served = &served;
+        let running = &running;
+        let max_running = &max_running;
+        async move {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now, Ordering::SeqCst);
+
+            let mut framed = Framed::new(stream)?;
+            let (msg, _info) = framed.next().await.unwrap()?;
+            assert_eq!(&msg[..], b"hello");
+            // let the handlers overlap
+            task::sleep(Duration::from_millis(100)).await;
+            served.fetch_add(1, Ordering::SeqCst);
+
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }));
+    assert!(res.is_ok());
+    assert_eq!(served.into_inner(), 3);
+    assert_eq!(max_running.into_inner(), 2);
+
+    client_thread.join().unwrap();
+}
+
+#[derive(Default)]
+struct Counter {
+    connected: AtomicUsize,
+    accepted: AtomicUsize,
+    broken: AtomicUsize,
+    closed: AtomicUsize,
+}
+
+impl Observer for Counter {
+    fn connected(&self, _sock: net::int, _peer: &SocketAddr) {
+        self.connected.fetch_add(1, Ordering::SeqCst);
+    }
+    fn accepted(&self, _listener: net::int, _sock: net::int, _peer: &SocketAddr) {
+        self.accepted.fetch_add(1, Ordering::SeqCst);
+    }
+    fn broken(&self, _sock: net::int) {
+        self.broken.fetch_add(1, Ordering::SeqCst);
+    }
+    fn closed(&self, _sock: net::int) {
+        self.closed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn stream_observer() {
+    let counter = Arc::new(Counter::default());
+    let builder = stream::Builder::new().unwrap().observer(counter.clone());
+
+    let try_addr = "127.0.0.1:0".parse().unwrap();
+    let server = builder.bind(&try_addr).unwrap();
+    let addr = server.local_addr().unwrap();
+    let mut incoming = builder.listen(server);
+
+    // the handshake completes on the reactor, which reports it
+    let client = task::block_on(builder.connect(addr)).unwrap();
+    let peer = task::block_on(incoming.next()).unwrap().unwrap();
+
+    assert_eq!(counter.connected.load(Ordering::SeqCst), 1);
+    assert_eq!(counter.accepted.load(Ordering::SeqCst), 1);
+
+    // the reactor polling the peer finds out, before anything is received
+    drop(client);
+    task::block_on(future::poll_fn(|cx| peer.poll_read_ready(cx))).unwrap();
+    assert_eq!(counter.broken.load(Ordering::SeqCst), 1);
+
+    // and receiving doesn't report it again
+    let mut peer = Framed::new(peer).unwrap();
+    assert!(task::block_on(peer.next()).is_none());
+    drop(peer);
+    assert_eq!(counter.broken.load(Ordering::SeqCst), 1);
+    assert_eq!(counter.closed.load(Ordering::SeqCst), 2);
+}